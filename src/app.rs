@@ -1,6 +1,6 @@
 use crate::{
     config::WeaveBuilder,
-    data::{io::write_parquet_col, types::AtomicF32},
+    data::{io::write_parquet_cols, types::AtomicF32},
     error::Result,
     model::Weave,
 };
@@ -19,7 +19,7 @@ impl Application {
 
     pub fn load_model(mut self, path: &str) -> Result<Self> {
         if self.model.is_none() {
-            self.model = Some(WeaveBuilder::from_toml(path)?.build());
+            self.model = Some(WeaveBuilder::from_toml(path)?.build()?);
         }
         Ok(self)
     }
@@ -68,7 +68,77 @@ impl Application {
             self.avg_multi_thread(num_threads)
         };
         let weave = self.model.as_ref().unwrap();
-        write_parquet_col(&weave.output.path, &weave.output.values, &result)?;
+        let labels: Vec<(&str, &[String])> = match (&weave.output.params, &weave.params) {
+            (Some(name), Some(params)) => vec![(name, params)],
+            _ => Vec::new(),
+        };
+        write_parquet_cols(&weave.output.path, &weave.output.values, &result, &labels)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{
+        io::{temp_path, write_parquet_col},
+        parquet::ParquetFileReader,
+    };
+    use std::fs;
+
+    fn write_config(name: &str, data: &str, pred: &str, output: &str, kernel: &str) -> String {
+        let path =
+            std::env::temp_dir().join(format!("weavers_test_{}_{}.toml", name, std::process::id()));
+        let config = format!(
+            "[input.data]\npath = \"{}\"\nvalues = \"x\"\n\n\
+             [input.pred]\npath = \"{}\"\n\n\
+             [output]\npath = \"{}\"\nvalues = \"prediction\"\nparams = \"params\"\n\n\
+             [[dimensions]]\nkind = \"GenericExponential\"\ncoord = [\"x\"]\nkernel = {}\n",
+            data, pred, output, kernel
+        );
+        fs::write(&path, config).unwrap();
+        path.to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_run_params() {
+        let data = temp_path("app_data");
+        let pred = temp_path("app_pred");
+        let output = temp_path("app_output");
+        write_parquet_col(&data, "x", &[1.0_f32, 2.0, 3.0]).unwrap();
+        write_parquet_col(&pred, "x", &[1.0_f32, 2.0]).unwrap();
+
+        // the prediction coordinate is also the radius of its row
+        let config = write_config("app", &data, &pred, &output, "{ radius_col = \"x\" }");
+        let app = Application::new().load_model(&config).unwrap();
+        app.run(1).unwrap();
+
+        let reader = ParquetFileReader::new(&output).unwrap();
+        let params = reader
+            .read_key_cols(&["params".to_string()])
+            .unwrap()
+            .to_vec();
+        assert_eq!(
+            params,
+            vec![
+                Some("x: radius=1".to_string()),
+                Some("x: radius=2".to_string())
+            ]
+        );
+        let prediction = reader.read_float_cols(&["prediction".to_string()]).unwrap();
+        assert_eq!(prediction.to_vec(), app.avg_single_thread());
+
+        // no parameters vary by prediction row
+        let fixed = write_config("app_fixed", &data, &pred, &output, "{ radius = 1.0 }");
+        assert!(Application::new().load_model(&fixed).is_err());
+
+        // the labels can't overwrite the prediction column
+        let labels = ["x".to_string()];
+        let labels: Vec<(&str, &[String])> = vec![("prediction", &labels)];
+        assert!(write_parquet_cols(&output, "prediction", &[1.0_f32], &labels).is_err());
+
+        for path in [data, pred, output, config, fixed] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -1,13 +1,25 @@
+use log::{info, warn};
+use parquet::basic::Type as PhysicalType;
 use serde::Deserialize;
-use std::fs;
+use std::{collections::HashMap, fs, slice, sync::Arc};
 use toml;
 
 use crate::{
-    data::{parquet::ParquetFileReader, types::Matrix},
-    error::Result,
+    data::{
+        category::Categories,
+        hierarchy::{pad_paths, Hierarchy},
+        parquet::ParquetFileReader,
+        types::Matrix,
+    },
+    error::{ConfigError, Result},
     model::{
         dimenion::{Dimension, DimensionHandle},
-        kernel::{Exponential, Leveled, Tricubic},
+        distance::{invert, DistanceTable, Metric, MISSING_LEVEL},
+        kernel::{
+            AdaptiveTricubic, Asymmetric, Bandwidth, BoxedKernel, Clamp, Compact, CompactShape,
+            Exponential, Gaussian, Indicator, InverseDistance, Leveled, Matern, MaternSmoothness,
+            Product, Rescale, Sum, Tricubic,
+        },
         Weave,
     },
 };
@@ -17,6 +29,13 @@ pub struct WeaveBuilder {
     pub input: Input,
     pub output: Output,
     pub dimensions: Vec<DimensionBuilder>,
+    /// Columns that partition the data and prediction rows into groups that
+    /// are weaved independently. Like the indicator kernel, a row with a
+    /// missing `by` value matches no other row, so its prediction is NaN.
+    /// Adaptive bandwidths (`k`, `quantile`) are chosen among the data rows of
+    /// the group only.
+    #[serde(default)]
+    pub by: Vec<String>,
 }
 
 impl WeaveBuilder {
@@ -26,20 +45,71 @@ impl WeaveBuilder {
         Ok(builder)
     }
 
-    pub fn build(self) -> Weave {
-        let data_reader = ParquetFileReader::new(&self.input.data.path).unwrap();
-        let pred_reader = ParquetFileReader::new(&self.input.pred.path).unwrap();
-        let dimensions: Vec<Dimension> = self
+    pub fn build(self) -> Result<Weave> {
+        let data_reader = ParquetFileReader::new(&self.input.data.path)?;
+        let pred_reader = ParquetFileReader::new(&self.input.pred.path)?;
+        let (dimensions, labels): (Vec<Dimension>, Vec<Option<Vec<String>>>) = self
             .dimensions
             .into_iter()
             .map(|dim_builder| dim_builder.build(&data_reader, &pred_reader))
-            .collect();
+            .collect::<Result<Vec<_>>>()?
+            .into_iter()
+            .unzip();
         let values = data_reader
-            .read_cols::<f32>(&[self.input.data.values])
-            .unwrap()
+            .read_cols::<f32>(&[self.input.data.values])?
             .to_vec();
         let lens = (data_reader.nrow(), pred_reader.nrow());
-        Weave::new(dimensions, values, lens, self.output)
+        let labels: Vec<Vec<String>> = labels.into_iter().flatten().collect();
+        if let Some(name) = &self.output.params {
+            if *name == self.output.values {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "params".to_string(),
+                    "has to differ from the output `values` column".to_string(),
+                )));
+            }
+            if labels.is_empty() {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "params".to_string(),
+                    "requires a dimension with kernel parameters by prediction row".to_string(),
+                )));
+            }
+        }
+        let params = self.output.params.is_some().then(|| {
+            (0..lens.1)
+                .map(|i| {
+                    let label: Vec<&str> = labels.iter().map(|l| l[i].as_str()).collect();
+                    label.join("; ")
+                })
+                .collect()
+        });
+        let mut weave = Weave::new(dimensions, values, lens, self.output);
+        weave.params = params;
+        if self.by.is_empty() {
+            return Ok(weave);
+        }
+        let (by_data, by_pred) = read_levels(&data_reader, &pred_reader, &self.by, None)?;
+        let mut categories = Categories::new();
+        let mut encode = |by: Matrix<i32>| -> Vec<Option<usize>> {
+            by.rows()
+                .map(|row| {
+                    (!row.contains(&MISSING_LEVEL)).then(|| categories.encode(&row.to_vec()))
+                })
+                .collect()
+        };
+        let groups_data = encode(by_data);
+        let groups_pred = encode(by_pred);
+        // rows with a missing value get a group of their own, the data and
+        // prediction rows never share it
+        let ngroups = categories.len();
+        let groups_data: Vec<usize> = groups_data
+            .into_iter()
+            .map(|g| g.unwrap_or(ngroups))
+            .collect();
+        let groups_pred: Vec<usize> = groups_pred
+            .into_iter()
+            .map(|g| g.unwrap_or(ngroups + 1))
+            .collect();
+        Ok(weave.with_groups((&groups_data, &groups_pred)))
     }
 }
 
@@ -64,49 +134,1352 @@ pub struct InputPred {
 pub struct Output {
     pub path: String,
     pub values: String,
+    /// Column for the kernel parameters that each prediction row used. Only
+    /// the parameters read by prediction row, i.e. `radius_col`,
+    /// `radius_table`, `exponent_col` and `exponent_table`, are labelled;
+    /// leveled and categorical kernels have the same weights for all rows.
+    pub params: Option<String>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DistanceKind {
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    Minkowski,
+    Haversine,
+    Cyclic,
+    Lookup,
+    Graph,
+    Mahalanobis,
+    Hierarchical,
+}
+
+impl DistanceKind {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Euclidean => "euclidean",
+            Self::Manhattan => "manhattan",
+            Self::Chebyshev => "chebyshev",
+            Self::Minkowski => "minkowski",
+            Self::Haversine => "haversine",
+            Self::Cyclic => "cyclic",
+            Self::Lookup => "lookup",
+            Self::Graph => "graph",
+            Self::Mahalanobis => "mahalanobis",
+            Self::Hierarchical => "hierarchical",
+        }
+    }
+}
+
+/// Distance table with the data and prediction ids encoded as its indices.
+type TableCoords = (DistanceTable, Matrix<usize>, Matrix<usize>);
+
+/// Distances supported by leveled kernels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum LeveledDistance {
+    /// Levels in columns or in the `hierarchy` table.
+    Hierarchical,
+    /// `lookup` and `graph` distance tables.
+    Table,
+}
+
+#[derive(Deserialize, Default)]
+pub struct MetricBuilder {
+    distance: Option<DistanceKind>,
+    p: Option<f32>,
+    period: Option<f32>,
+    table: Option<String>,
+    graph: Option<String>,
+    hierarchy: Option<String>,
+    /// Id columns of the `table` and `graph` files, `["id_a", "id_b"]` by
+    /// default.
+    pair_cols: Option<Vec<String>>,
+    /// Distance column of the `table` file, `distance` by default.
+    distance_col: Option<String>,
+    /// Edge weight column of the `graph` file, `weight` by default. Without
+    /// a weight column the distance is the number of hops.
+    weight_col: Option<String>,
+    /// Parent id column of the `hierarchy` file, `parent_id` by default.
+    parent_col: Option<String>,
+    missing: Option<i32>,
+    scale: Option<Vec<f32>>,
+    #[serde(default)]
+    standardize: bool,
+    covariance: Option<Vec<Vec<f32>>>,
+}
+impl MetricBuilder {
+    /// Metric for continuous kernels, defaults to `euclidean`. `mahalanobis`
+    /// depends on the coordinates and is built by [`Self::build_coords`].
+    fn build(self) -> Result<Metric> {
+        let metric = match self.distance.unwrap_or(DistanceKind::Euclidean) {
+            DistanceKind::Euclidean => Metric::Euclidean,
+            DistanceKind::Manhattan => Metric::Manhattan,
+            DistanceKind::Chebyshev => Metric::Chebyshev,
+            DistanceKind::Minkowski => {
+                let p = self.p.ok_or(ConfigError::MissingParameter(
+                    "minkowski".to_string(),
+                    "p".to_string(),
+                ))?;
+                if p < 1.0 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "p".to_string(),
+                        "has to be no less than one".to_string(),
+                    )));
+                }
+                Metric::Minkowski(p)
+            }
+            DistanceKind::Haversine => Metric::Haversine,
+            DistanceKind::Cyclic => {
+                let period = self.period.ok_or(ConfigError::MissingParameter(
+                    "cyclic".to_string(),
+                    "period".to_string(),
+                ))?;
+                if period <= 0.0 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "period".to_string(),
+                        "has to be positive".to_string(),
+                    )));
+                }
+                Metric::Cyclic(period)
+            }
+            DistanceKind::Mahalanobis => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "distance".to_string(),
+                    "`mahalanobis` is built from the `coord` columns".to_string(),
+                )))
+            }
+            kind => {
+                return Err(Box::new(ConfigError::UnsupportedDistance(
+                    kind.name().to_string(),
+                    "continuous".to_string(),
+                )))
+            }
+        };
+        Ok(metric)
+    }
+
+    /// Build the metric and read the continuous coordinates it applies to.
+    /// `FLOAT` and `DOUBLE` columns are both accepted. Each column is divided
+    /// by its `scale`, or by its standard deviation in the data with
+    /// `standardize`. `mahalanobis` distance is not scaled, the covariance
+    /// already accounts for the scales of the columns.
+    pub fn build_coords(
+        self,
+        coord: &[String],
+        data_reader: &ParquetFileReader,
+        pred_reader: &ParquetFileReader,
+    ) -> Result<(Metric, Matrix<f32>, Matrix<f32>)> {
+        let scaled = self.scale.is_some() || self.standardize;
+        let scaled_kind = matches!(
+            self.distance,
+            Some(
+                DistanceKind::Haversine
+                    | DistanceKind::Cyclic
+                    | DistanceKind::Lookup
+                    | DistanceKind::Graph
+                    | DistanceKind::Mahalanobis
+            )
+        );
+        if scaled && scaled_kind {
+            return Err(Box::new(ConfigError::UnsupportedDistance(
+                self.distance.unwrap().name().to_string(),
+                "scaled".to_string(),
+            )));
+        }
+        if let Some((table, codes_data, codes_pred)) =
+            self.build_table(coord, data_reader, pred_reader)?
+        {
+            let metric = Metric::Table(Arc::new(table));
+            return Ok((
+                metric,
+                codes_data.map(|code| code as f32),
+                codes_pred.map(|code| code as f32),
+            ));
+        }
+        if self.distance == Some(DistanceKind::Haversine) && coord.len() != 2 {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "coord".to_string(),
+                "haversine distance requires two columns `[lat, lon]`".to_string(),
+            )));
+        }
+        let coord_data = data_reader.read_float_cols(coord)?;
+        let coord_pred = pred_reader.read_float_cols(coord)?;
+        if self.distance == Some(DistanceKind::Mahalanobis) {
+            let metric = build_mahalanobis(self.covariance, &coord_data)?;
+            return Ok((metric, coord_data, coord_pred));
+        }
+        let (scale, standardize) = (self.scale.clone(), self.standardize);
+        let metric = self.build()?;
+        let scale = match (scale, standardize) {
+            (None, false) => return Ok((metric, coord_data, coord_pred)),
+            (Some(scale), false) => {
+                if scale.len() != coord.len() || scale.iter().any(|x| *x <= 0.0) {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "scale".to_string(),
+                        "requires one positive value per `coord` column".to_string(),
+                    )));
+                }
+                scale
+            }
+            (None, true) => col_std(&coord_data),
+            (Some(_), true) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "scale".to_string(),
+                    "can't be used together with `standardize`".to_string(),
+                )))
+            }
+        };
+        info!("scales of {:?}: {:?}", coord, scale);
+        Ok((
+            metric,
+            scale_cols(coord_data, &scale),
+            scale_cols(coord_pred, &scale),
+        ))
+    }
+
+    /// Read the coordinates of a leveled kernel. With `lookup` and `graph`
+    /// distances the levels come from the returned distance table, otherwise
+    /// the distance is `hierarchical`, either on one column per level or on
+    /// the ancestors of a single id column in the `hierarchy` table. Nulls in
+    /// the level columns, and values equal to `missing`, are missing levels.
+    pub fn build_levels(
+        self,
+        coord: &[String],
+        data_reader: &ParquetFileReader,
+        pred_reader: &ParquetFileReader,
+    ) -> Result<(Option<DistanceTable>, Matrix<i32>, Matrix<i32>)> {
+        match self.check_leveled()? {
+            LeveledDistance::Hierarchical => match &self.hierarchy {
+                Some(path) => {
+                    let ids_data = read_ids(data_reader, coord, self.missing)?;
+                    let ids_pred = read_ids(pred_reader, coord, self.missing)?;
+                    let parent_col = self.parent_col.as_deref().unwrap_or("parent_id");
+                    let (coord_data, coord_pred) =
+                        read_hierarchy(path, coord, parent_col, ids_data, ids_pred)?;
+                    Ok((None, coord_data, coord_pred))
+                }
+                None => {
+                    let (coord_data, coord_pred) =
+                        read_levels(data_reader, pred_reader, coord, self.missing)?;
+                    Ok((None, coord_data, coord_pred))
+                }
+            },
+            LeveledDistance::Table => {
+                let (table, codes_data, codes_pred) =
+                    self.build_table(coord, data_reader, pred_reader)?.unwrap();
+                Ok((
+                    Some(table),
+                    codes_data.map(|code| code as i32),
+                    codes_pred.map(|code| code as i32),
+                ))
+            }
+        }
+    }
+
+    /// Leveled kernels work with `hierarchical`, `lookup` and `graph`
+    /// distances, defaults to `hierarchical`.
+    fn check_leveled(&self) -> Result<LeveledDistance> {
+        match self.distance.unwrap_or(DistanceKind::Hierarchical) {
+            DistanceKind::Hierarchical => Ok(LeveledDistance::Hierarchical),
+            DistanceKind::Lookup | DistanceKind::Graph => Ok(LeveledDistance::Table),
+            kind => Err(Box::new(ConfigError::UnsupportedDistance(
+                kind.name().to_string(),
+                "leveled".to_string(),
+            ))),
+        }
+    }
+
+    /// Distance table for `lookup` and `graph` distances, along with the ids
+    /// of the data and prediction rows encoded as indices into the table.
+    fn build_table(
+        &self,
+        coord: &[String],
+        data_reader: &ParquetFileReader,
+        pred_reader: &ParquetFileReader,
+    ) -> Result<Option<TableCoords>> {
+        let (kind, path, param) = match self.distance {
+            Some(DistanceKind::Lookup) => (DistanceKind::Lookup, &self.table, "table"),
+            Some(DistanceKind::Graph) => (DistanceKind::Graph, &self.graph, "graph"),
+            _ => return Ok(None),
+        };
+        let path = path.as_ref().ok_or(ConfigError::MissingParameter(
+            kind.name().to_string(),
+            param.to_string(),
+        ))?;
+        if coord.len() != 1 {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "coord".to_string(),
+                format!("{} distance requires a single id column", kind.name()),
+            )));
+        }
+        let mut categories = Categories::new();
+        let codes_data = encode_ids(&mut categories, data_reader.read_cols::<i32>(coord)?);
+        let codes_pred = encode_ids(&mut categories, pred_reader.read_cols::<i32>(coord)?);
+        let (unique_data, unique_pred) = (unique_codes(&codes_data), unique_codes(&codes_pred));
+
+        let pair_cols = match &self.pair_cols {
+            Some(cols) if cols.len() == 2 => cols.clone(),
+            Some(_) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "pair_cols".to_string(),
+                    "requires exactly two id columns".to_string(),
+                )))
+            }
+            None => vec!["id_a".to_string(), "id_b".to_string()],
+        };
+        let table_reader = ParquetFileReader::new(path)?;
+        let table = if kind == DistanceKind::Lookup {
+            let distance_col = self.distance_col.as_deref().unwrap_or("distance");
+            read_lookup(&table_reader, &categories, &pair_cols, distance_col)?
+        } else {
+            read_graph(
+                &table_reader,
+                &mut categories,
+                &unique_pred,
+                &pair_cols,
+                self.weight_col.as_deref(),
+            )?
+        };
+
+        if let Some((a, b)) = table.missing_pair(&unique_pred, &unique_data) {
+            return Err(Box::new(ConfigError::MissingDistance(
+                categories.value(a).to_string(),
+                categories.value(b).to_string(),
+                path.to_string(),
+            )));
+        }
+        Ok(Some((table, codes_data, codes_pred)))
+    }
+}
+
+/// Mahalanobis metric from the `covariance` matrix, estimated from the data
+/// coordinates when it is not given.
+fn build_mahalanobis(
+    covariance: Option<Vec<Vec<f32>>>,
+    coord_data: &Matrix<f32>,
+) -> Result<Metric> {
+    let n = coord_data.ncols;
+    let covariance = match covariance {
+        Some(rows) => {
+            if rows.len() != n || rows.iter().any(|row| row.len() != n) {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "covariance".to_string(),
+                    format!("has to be {} by {} for the `coord` columns", n, n),
+                )));
+            }
+            rows.concat()
+        }
+        None => col_cov(coord_data),
+    };
+    let precision = invert(&covariance, n).ok_or(ConfigError::InvalidParameter(
+        "covariance".to_string(),
+        "is singular".to_string(),
+    ))?;
+    info!("mahalanobis covariance: {:?}", covariance);
+    Ok(Metric::Mahalanobis(Arc::new(precision)))
+}
+
+/// Covariance matrix of the columns, stored row by row.
+fn col_cov(coords: &Matrix<f32>) -> Vec<f32> {
+    let (n, ncols) = (coords.rows().len() as f32, coords.ncols);
+    let mut mean = vec![0.0_f32; ncols];
+    coords.rows().for_each(|row| {
+        row.iter()
+            .zip(mean.iter_mut())
+            .for_each(|(x, m)| *m += x / n)
+    });
+    let mut cov = vec![0.0_f32; ncols * ncols];
+    coords.rows().for_each(|row| {
+        (0..ncols * ncols).for_each(|k| {
+            let (i, j) = (k / ncols, k % ncols);
+            cov[k] += (row[i] - mean[i]) * (row[j] - mean[j]) / n;
+        })
+    });
+    cov
+}
+
+/// Standard deviation of each column, one for constant columns.
+fn col_std(coords: &Matrix<f32>) -> Vec<f32> {
+    let n = coords.rows().len() as f32;
+    let mut mean = vec![0.0_f32; coords.ncols];
+    coords.rows().for_each(|row| {
+        row.iter()
+            .zip(mean.iter_mut())
+            .for_each(|(x, m)| *m += x / n)
+    });
+    let mut var = vec![0.0_f32; coords.ncols];
+    coords.rows().for_each(|row| {
+        row.iter()
+            .zip(mean.iter())
+            .zip(var.iter_mut())
+            .for_each(|((x, m), v)| *v += (x - m) * (x - m) / n)
+    });
+    var.iter()
+        .map(|v| if *v > 0.0 { v.sqrt() } else { 1.0 })
+        .collect()
+}
+
+fn scale_cols(coords: Matrix<f32>, scale: &[f32]) -> Matrix<f32> {
+    let ncols = coords.ncols;
+    let vec = coords
+        .vec
+        .iter()
+        .enumerate()
+        .map(|(i, x)| x / scale[i % ncols])
+        .collect();
+    Matrix::new(vec, ncols)
+}
+
+/// Read the level columns of the data and prediction rows. Integer columns are
+/// used as they are, otherwise the levels, e.g. strings, are encoded into codes
+/// shared by the data and prediction rows.
+fn read_levels(
+    data_reader: &ParquetFileReader,
+    pred_reader: &ParquetFileReader,
+    coord: &[String],
+    missing: Option<i32>,
+) -> Result<(Matrix<i32>, Matrix<i32>)> {
+    let is_int = |reader: &ParquetFileReader| -> Result<bool> {
+        Ok(reader
+            .col_types(coord)?
+            .iter()
+            .all(|t| *t == PhysicalType::INT32))
+    };
+    if is_int(data_reader)? && is_int(pred_reader)? {
+        return Ok((
+            read_ids(data_reader, coord, missing)?,
+            read_ids(pred_reader, coord, missing)?,
+        ));
+    }
+    let missing = missing.map(|missing| missing.to_string());
+    let mut categories = Categories::new();
+    let mut encode = |keys: Matrix<Option<String>>| {
+        keys.map(|key| match key {
+            Some(key) if Some(&key) != missing.as_ref() => categories.encode(&key) as i32,
+            _ => MISSING_LEVEL,
+        })
+    };
+    let coord_data = encode(data_reader.read_key_cols(coord)?);
+    let coord_pred = encode(pred_reader.read_key_cols(coord)?);
+    Ok((coord_data, coord_pred))
+}
+
+/// Integer ids of the `coord` columns, where nulls and values equal to
+/// `missing` become [`MISSING_LEVEL`].
+fn read_ids(
+    reader: &ParquetFileReader,
+    coord: &[String],
+    missing: Option<i32>,
+) -> Result<Matrix<i32>> {
+    let ids = reader.read_cols_or::<i32>(coord, MISSING_LEVEL)?;
+    Ok(match missing {
+        Some(missing) => ids.map(|x| if x == missing { MISSING_LEVEL } else { x }),
+        None => ids,
+    })
+}
+
+/// Levels of the ids from the `(<coord>, <parent_col>)` table at `path`, with
+/// as many levels as the deepest id in the data and prediction rows.
+fn read_hierarchy(
+    path: &str,
+    coord: &[String],
+    parent_col: &str,
+    ids_data: Matrix<i32>,
+    ids_pred: Matrix<i32>,
+) -> Result<(Matrix<i32>, Matrix<i32>)> {
+    if coord.len() != 1 {
+        return Err(Box::new(ConfigError::InvalidParameter(
+            "coord".to_string(),
+            "`hierarchy` requires a single id column".to_string(),
+        )));
+    }
+    let reader = ParquetFileReader::new(path)?;
+    let tree = reader
+        .read_cols_or::<i32>(&[coord[0].clone(), parent_col.to_string()], MISSING_LEVEL)?
+        .to_vec();
+    // ids without a parent are roots, their own parent
+    let (ids, parent_ids): (Vec<i32>, Vec<i32>) = tree
+        .chunks(2)
+        .filter(|row| row[0] != MISSING_LEVEL)
+        .map(|row| match row[1] {
+            MISSING_LEVEL => (row[0], row[0]),
+            parent_id => (row[0], parent_id),
+        })
+        .unzip();
+    let hierarchy = Hierarchy::new(&ids, &parent_ids);
+
+    let paths = |ids: &[i32]| -> Result<Vec<Vec<i32>>> {
+        ids.iter()
+            .map(|id| match *id {
+                MISSING_LEVEL => Ok(vec![MISSING_LEVEL]),
+                id => Ok(hierarchy.path(id)?),
+            })
+            .collect()
+    };
+    let paths_data = paths(&ids_data.vec)?;
+    let paths_pred = paths(&ids_pred.vec)?;
+    let depth = paths_data
+        .iter()
+        .chain(paths_pred.iter())
+        .map(|path| path.len())
+        .max()
+        .unwrap_or(1);
+    Ok((pad_paths(&paths_data, depth), pad_paths(&paths_pred, depth)))
+}
+
+/// Distances from the `(<pair_cols>, <distance_col>)` table, pairs are
+/// symmetric.
+fn read_lookup(
+    reader: &ParquetFileReader,
+    categories: &Categories<i32>,
+    pair_cols: &[String],
+    distance_col: &str,
+) -> Result<DistanceTable> {
+    let pairs = reader.read_cols::<i32>(pair_cols)?;
+    let values = reader.read_float_cols(&[distance_col.to_string()])?;
+    if values.vec.iter().any(|d| d.is_nan() || *d < 0.0) {
+        return Err(Box::new(ConfigError::InvalidParameter(
+            distance_col.to_string(),
+            "lookup distances have to be non-negative".to_string(),
+        )));
+    }
+    let mut table = DistanceTable::new(categories.len());
+    pairs.rows().zip(values.vec.iter()).for_each(|(pair, d)| {
+        if let (Some(a), Some(b)) = (categories.get(&pair[0]), categories.get(&pair[1])) {
+            table.set(a, b, *d);
+        }
+    });
+    Ok(table)
+}
+
+/// Shortest path distances in the undirected graph given by the edge list
+/// `pair_cols`. Edges are weighted by the `weight_col` column, `weight` by
+/// default when there is one, otherwise the distance is the number of hops.
+fn read_graph(
+    reader: &ParquetFileReader,
+    categories: &mut Categories<i32>,
+    sources: &[usize],
+    pair_cols: &[String],
+    weight_col: Option<&str>,
+) -> Result<DistanceTable> {
+    let size = categories.len();
+    let pairs = reader.read_cols::<i32>(pair_cols)?;
+    let weight_col = match weight_col {
+        Some(col) => Some(col),
+        None => reader.cols().find(|col| *col == "weight"),
+    };
+    let weights = if let Some(weight_col) = weight_col {
+        let weights = reader.read_float_cols(&[weight_col.to_string()])?.to_vec();
+        if weights.iter().any(|w| *w < 0.0) {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "weight_col".to_string(),
+                "graph edge weights have to be non-negative".to_string(),
+            )));
+        }
+        weights
+    } else {
+        vec![1.0; pairs.vec.len() / 2]
+    };
+    let edges: Vec<(usize, usize, f32)> = pairs
+        .rows()
+        .zip(weights.iter())
+        .map(|(pair, w)| (categories.encode(&pair[0]), categories.encode(&pair[1]), *w))
+        .collect();
+    Ok(DistanceTable::from_graph(
+        size,
+        categories.len(),
+        &edges,
+        sources,
+    ))
+}
+
+fn encode_ids(categories: &mut Categories<i32>, ids: Matrix<i32>) -> Matrix<usize> {
+    let ncols = ids.ncols;
+    let codes = ids.vec.iter().map(|id| categories.encode(id)).collect();
+    Matrix::new(codes, ncols)
+}
+
+fn unique_codes(codes: &Matrix<usize>) -> Vec<usize> {
+    let mut codes = codes.vec.clone();
+    codes.sort_unstable();
+    codes.dedup();
+    codes
+}
+
+/// Radius of a continuous kernel, either fixed, read from the column
+/// `radius_col` of the prediction file, or looked up by the stratum of each
+/// prediction row in `radius_table`. Like `radius`, the radius read by row is
+/// in the units of the coordinates after `scale` or `standardize`. Only
+/// symmetric, non-adaptive continuous kernels take their radius by row.
+#[derive(Deserialize)]
+pub struct RadiusBuilder {
+    radius: Option<f32>,
+    radius_col: Option<String>,
+    radius_table: Option<StrataBuilder>,
+}
+impl RadiusBuilder {
+    /// Fixed radius, one is a placeholder when the radius is read by row.
+    fn build(&self) -> Result<f32> {
+        match (self.radius, self.by_row()) {
+            (Some(radius), false) if radius > 0.0 => Ok(radius),
+            (Some(_), false) => Err(Box::new(ConfigError::InvalidParameter(
+                "radius".to_string(),
+                "has to be positive".to_string(),
+            ))),
+            (None, true) => Ok(1.0),
+            _ => Err(Box::new(ConfigError::InvalidParameter(
+                "radius".to_string(),
+                "exactly one of `radius`, `radius_col` and `radius_table` has to be given"
+                    .to_string(),
+            ))),
+        }
+    }
+
+    fn by_row(&self) -> bool {
+        self.radius_col.is_some() || self.radius_table.is_some()
+    }
+
+    fn read(&self, pred_reader: &ParquetFileReader) -> Result<Option<Vec<f32>>> {
+        read_pred_param(
+            pred_reader,
+            "radius",
+            self.radius_col.as_deref(),
+            self.radius_table.as_ref(),
+            false,
+        )
+    }
+}
+
+/// Kernel parameter by stratum, e.g. a time radius by age group. The strata
+/// are the `INT32` or `INT64` column `by` of the prediction file. The parameters are
+/// read from the columns `by` and the parameter name of the Parquet file
+/// `path`, or given inline as `strata` and `values`.
+#[derive(Deserialize)]
+pub struct StrataBuilder {
+    by: String,
+    path: Option<String>,
+    strata: Option<Vec<i64>>,
+    values: Option<Vec<f32>>,
+}
+impl StrataBuilder {
+    /// Join the parameter `name` onto the prediction rows by their stratum.
+    fn join(&self, name: &str, pred_reader: &ParquetFileReader) -> Result<Vec<f32>> {
+        let (strata, values) = match (&self.path, &self.strata, &self.values) {
+            (Some(path), None, None) => {
+                let reader = ParquetFileReader::new(path)?;
+                let strata = reader.read_int_cols(slice::from_ref(&self.by))?.to_vec();
+                let values = reader.read_float_cols(&[name.to_string()])?.to_vec();
+                (strata, values)
+            }
+            (None, Some(strata), Some(values)) if strata.len() == values.len() => {
+                (strata.clone(), values.clone())
+            }
+            _ => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    format!("{}_table", name),
+                    "requires either `path`, or `strata` and `values` of equal length".to_string(),
+                )))
+            }
+        };
+        let mut table: HashMap<i64, f32> = HashMap::new();
+        for (stratum, value) in strata.into_iter().zip(values) {
+            if table.insert(stratum, value).is_some() {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    format!("{}_table", name),
+                    format!("stratum {} is given more than once", stratum),
+                )));
+            }
+        }
+        pred_reader
+            .read_int_cols(slice::from_ref(&self.by))?
+            .to_vec()
+            .iter()
+            .map(|stratum| {
+                table.get(stratum).copied().ok_or_else(|| {
+                    ConfigError::MissingStratum(
+                        name.to_string(),
+                        self.by.clone(),
+                        stratum.to_string(),
+                    )
+                    .into()
+                })
+            })
+            .collect()
+    }
+}
+
+/// Kernel parameter `name` of each prediction row, read from the column `col`
+/// or joined from the stratified `table`. The parameter has to be positive,
+/// or non-negative with `allow_zero`.
+fn read_pred_param(
+    pred_reader: &ParquetFileReader,
+    name: &str,
+    col: Option<&str>,
+    table: Option<&StrataBuilder>,
+    allow_zero: bool,
+) -> Result<Option<Vec<f32>>> {
+    let values = match (col, table) {
+        (None, None) => return Ok(None),
+        (Some(col), None) => pred_reader.read_float_cols(&[col.to_string()])?.to_vec(),
+        (None, Some(table)) => table.join(name, pred_reader)?,
+        (Some(_), Some(_)) => {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                format!("{}_col", name),
+                format!("can't be used together with `{}_table`", name),
+            )))
+        }
+    };
+    if values
+        .iter()
+        .any(|x| x.is_nan() || *x < 0.0 || (*x == 0.0 && !allow_zero))
+    {
+        let bound = if allow_zero {
+            "non-negative"
+        } else {
+            "positive"
+        };
+        return Err(Box::new(ConfigError::InvalidParameter(
+            name.to_string(),
+            format!("has to be {} in all prediction rows", bound),
+        )));
+    }
+    Ok(Some(values))
+}
+
+/// Label of the kernel parameters of each prediction row, e.g.
+/// `year_id: radius=2`.
+fn param_labels(coord: &[String], params: &[(&str, &[f32])]) -> Vec<String> {
+    let npred = params.first().map_or(0, |(_, values)| values.len());
+    (0..npred)
+        .map(|i| {
+            let values: Vec<String> = params
+                .iter()
+                .map(|(name, values)| format!("{}={}", name, values[i]))
+                .collect();
+            format!("{}: {}", coord.join(","), values.join(", "))
+        })
+        .collect()
 }
 
 #[derive(Deserialize)]
 pub struct ExponentialBuilder {
-    radius: f32,
+    #[serde(flatten)]
+    radius: RadiusBuilder,
 }
 impl ExponentialBuilder {
-    pub fn build(self) -> Exponential {
-        Exponential::new(self.radius)
+    pub fn build(&self, metric: Metric) -> Result<Exponential> {
+        Ok(Exponential::new(self.radius.build()?).with_metric(metric))
     }
 }
 
+/// Tricubic kernel, the radius defaults to cover all data for every
+/// prediction point. `radius_col` and `exponent_col` read the parameters of
+/// each prediction row from the prediction file instead, and `radius_table`
+/// and `exponent_table` look them up by stratum.
 #[derive(Deserialize)]
 pub struct TricubicBuilder {
     radius: Option<f32>,
-    exponent: f32,
+    radius_col: Option<String>,
+    radius_table: Option<StrataBuilder>,
+    exponent: Option<f32>,
+    exponent_col: Option<String>,
+    exponent_table: Option<StrataBuilder>,
+    #[serde(default)]
+    allow_negative: bool,
 }
 impl TricubicBuilder {
-    fn build(self, coord_data: &Matrix<f32>, coord_pred: &Matrix<f32>) -> Tricubic {
-        let radius = match self.radius {
-            Some(x) => x,
-            None => {
-                let (data_min, data_max) = partialord_min_max(&coord_data);
-                let (pred_min, pred_max) = partialord_min_max(&coord_pred);
-                let (diff0, diff1) = (data_max - pred_min, pred_max - data_min);
-                if diff0 > diff1 {
-                    diff0 + 1.0
-                } else {
-                    diff1 + 1.0
+    fn build(
+        &self,
+        metric: Metric,
+        coord_data: &Matrix<f32>,
+        coord_pred: &Matrix<f32>,
+    ) -> Result<Tricubic> {
+        let exponent_by_row = self.exponent_col.is_some() || self.exponent_table.is_some();
+        let exponent = match (self.exponent, exponent_by_row) {
+            (Some(exponent), false) => exponent,
+            // placeholder, the exponent is read by row
+            (None, true) => 1.0,
+            _ => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "exponent".to_string(),
+                    "exactly one of `exponent`, `exponent_col` and `exponent_table` has to be \
+                     given"
+                        .to_string(),
+                )))
+            }
+        };
+        let radius_by_row = self.radius_col.is_some() || self.radius_table.is_some();
+        let data_min_max = partialord_min_max(coord_data);
+        let pred_min_max = partialord_min_max(coord_pred);
+        let radius = match (self.radius, radius_by_row) {
+            (Some(_), true) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "radius".to_string(),
+                    "can't be used together with `radius_col` or `radius_table`".to_string(),
+                )))
+            }
+            (Some(x), false) if x <= 0.0 => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "radius".to_string(),
+                    "has to be positive".to_string(),
+                )))
+            }
+            (Some(x), false) => {
+                check_support(&[x], &metric, &data_min_max, coord_pred);
+                x
+            }
+            // placeholder, the radius is read by row
+            (None, true) => 1.0,
+            (None, false) => {
+                let span: Vec<f32> = data_min_max
+                    .iter()
+                    .zip(pred_min_max.iter())
+                    .map(|((data_min, data_max), (pred_min, pred_max))| {
+                        let (diff0, diff1) = (*data_max - *pred_min, *pred_max - *data_min);
+                        if diff0 > diff1 {
+                            diff0
+                        } else {
+                            diff1
+                        }
+                    })
+                    .collect();
+                metric.bound(&span) + 1.0
+            }
+        };
+        check_allow_negative(self.allow_negative, &metric)?;
+        Ok(Tricubic::new(radius, exponent)
+            .with_metric(metric)
+            .with_allow_negative(self.allow_negative))
+    }
+
+    fn by_row(&self) -> bool {
+        self.radius_col.is_some()
+            || self.radius_table.is_some()
+            || self.exponent_col.is_some()
+            || self.exponent_table.is_some()
+    }
+
+    /// Kernel of each prediction row, when the radius or the exponent is read
+    /// by row.
+    fn read(
+        &self,
+        kernel: &Tricubic,
+        pred_reader: &ParquetFileReader,
+    ) -> Result<Option<Vec<Tricubic>>> {
+        let radius = read_pred_param(
+            pred_reader,
+            "radius",
+            self.radius_col.as_deref(),
+            self.radius_table.as_ref(),
+            false,
+        )?;
+        let exponent = read_pred_param(
+            pred_reader,
+            "exponent",
+            self.exponent_col.as_deref(),
+            self.exponent_table.as_ref(),
+            true,
+        )?;
+        if radius.is_none() && exponent.is_none() {
+            return Ok(None);
+        }
+        let npred = pred_reader.nrow();
+        let radius = radius.unwrap_or_else(|| vec![kernel.radius; npred]);
+        let exponent = exponent.unwrap_or_else(|| vec![kernel.exponent; npred]);
+        let kernels = radius
+            .iter()
+            .zip(exponent.iter())
+            .map(|(r, e)| {
+                Tricubic::new(*r, *e)
+                    .with_metric(kernel.metric.clone())
+                    .with_allow_negative(kernel.allow_negative)
+            })
+            .collect();
+        Ok(Some(kernels))
+    }
+}
+
+/// Warn when a prediction point lies further than its `radius` outside the
+/// range of the data coordinates, where no data is in the kernel support.
+/// `radius` is either a single radius or one radius per prediction row.
+fn check_support(
+    radius: &[f32],
+    metric: &Metric,
+    data_min_max: &[(&f32, &f32)],
+    coord_pred: &Matrix<f32>,
+) {
+    if matches!(
+        metric,
+        Metric::Haversine | Metric::Cyclic(_) | Metric::Table(_) | Metric::Mahalanobis(_)
+    ) {
+        return;
+    }
+    let outside = coord_pred.rows().enumerate().find_map(|(i, x)| {
+        let gap: Vec<f32> = data_min_max
+            .iter()
+            .zip(x.iter())
+            .map(|((data_min, data_max), xi)| (*data_min - xi).max(xi - *data_max).max(0.0))
+            .collect();
+        let distance = metric.bound(&gap);
+        let radius = radius[i.min(radius.len() - 1)];
+        (distance > radius).then_some((i, distance, radius))
+    });
+    if let Some((i, distance, radius)) = outside {
+        warn!(
+            "prediction row {} lies {} outside the data range, beyond tricubic `radius` = {}",
+            i, distance, radius
+        );
+    }
+}
+
+/// Kernels whose parameters can't be read by prediction row.
+fn reject_by_row(
+    kind: &str,
+    radius_col: &Option<String>,
+    radius_table: &Option<StrataBuilder>,
+) -> Result<()> {
+    if radius_col.is_some() || radius_table.is_some() {
+        return Err(Box::new(ConfigError::InvalidParameter(
+            "radius_col".to_string(),
+            format!(
+                "kernel parameters by prediction row are not supported by {} kernels",
+                kind
+            ),
+        )));
+    }
+    Ok(())
+}
+
+/// Negative tricubic weights are unbounded on `lookup` and `graph` distances,
+/// where unreachable pairs are at infinity.
+fn check_allow_negative(allow_negative: bool, metric: &Metric) -> Result<()> {
+    if allow_negative && matches!(metric, Metric::Table(_)) {
+        return Err(Box::new(ConfigError::InvalidParameter(
+            "allow_negative".to_string(),
+            "can't be used with `lookup` or `graph` distance".to_string(),
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Deserialize)]
+pub struct AdaptiveTricubicBuilder {
+    /// Not supported, the radius is chosen for each prediction point.
+    radius: Option<f32>,
+    /// Not supported, as `radius`.
+    radius_col: Option<String>,
+    /// Not supported, as `radius`.
+    radius_table: Option<StrataBuilder>,
+    exponent: f32,
+    k: Option<usize>,
+    quantile: Option<f32>,
+    #[serde(default)]
+    allow_negative: bool,
+}
+impl AdaptiveTricubicBuilder {
+    pub fn build(self, metric: Metric) -> Result<AdaptiveTricubic> {
+        reject_by_row("adaptive", &self.radius_col, &self.radius_table)?;
+        if self.radius.is_some() {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "radius".to_string(),
+                "adaptive kernels choose the radius by `k` or `quantile`".to_string(),
+            )));
+        }
+        let bandwidth = match (self.k, self.quantile) {
+            (None, None) => Bandwidth::Max,
+            (Some(k), None) => {
+                if k < 1 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "k".to_string(),
+                        "has to be positive".to_string(),
+                    )));
+                }
+                Bandwidth::Nearest(k)
+            }
+            (None, Some(q)) => {
+                if !(q > 0.0 && q <= 1.0) {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "quantile".to_string(),
+                        "has to be in (0, 1]".to_string(),
+                    )));
                 }
+                Bandwidth::Quantile(q)
             }
+            (Some(_), Some(_)) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "k".to_string(),
+                    "can't be used together with `quantile`".to_string(),
+                )))
+            }
+        };
+        // unreachable ids are at infinity, which leaves no finite radius
+        if matches!(metric, Metric::Table(_)) {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "distance".to_string(),
+                "adaptive kernels don't support `lookup` or `graph` distance".to_string(),
+            )));
+        }
+        // the radius is chosen for each prediction point, one is a placeholder
+        let tricubic = Tricubic::new(1.0, self.exponent)
+            .with_metric(metric)
+            .with_allow_negative(self.allow_negative);
+        Ok(AdaptiveTricubic::new(tricubic, bandwidth))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct GaussianBuilder {
+    #[serde(flatten)]
+    radius: RadiusBuilder,
+}
+impl GaussianBuilder {
+    pub fn build(&self, metric: Metric) -> Result<Gaussian> {
+        Ok(Gaussian::new(self.radius.build()?).with_metric(metric))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CompactBuilder {
+    #[serde(flatten)]
+    radius: RadiusBuilder,
+}
+impl CompactBuilder {
+    pub fn build(&self, shape: CompactShape, metric: Metric) -> Result<Compact> {
+        Ok(Compact::new(self.radius.build()?, shape).with_metric(metric))
+    }
+}
+
+/// Matérn kernel with length scale `radius` and smoothness `nu`, one of
+/// 0.5, 1.5 and 2.5.
+#[derive(Deserialize)]
+pub struct MaternBuilder {
+    #[serde(flatten)]
+    radius: RadiusBuilder,
+    nu: f32,
+}
+impl MaternBuilder {
+    pub fn build(&self, metric: Metric) -> Result<Matern> {
+        let nu = if self.nu == 0.5 {
+            MaternSmoothness::Half
+        } else if self.nu == 1.5 {
+            MaternSmoothness::ThreeHalves
+        } else if self.nu == 2.5 {
+            MaternSmoothness::FiveHalves
+        } else {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "nu".to_string(),
+                "has to be one of 0.5, 1.5 and 2.5".to_string(),
+            )));
         };
-        Tricubic::new(radius, self.exponent)
+        Ok(Matern::new(self.radius.build()?, nu).with_metric(metric))
+    }
+}
+
+/// Inverse-distance weighting with `power` defaulting to 2 and `eps` to 0.
+/// Exact matches get an infinite weight unless `eps` is positive or their
+/// weight is capped at `max_weight`.
+#[derive(Deserialize)]
+pub struct InverseDistanceBuilder {
+    power: Option<f32>,
+    #[serde(default)]
+    eps: f32,
+    max_weight: Option<f32>,
+}
+impl InverseDistanceBuilder {
+    pub fn build(self, metric: Metric) -> Result<InverseDistance> {
+        let power = self.power.unwrap_or(2.0);
+        if power <= 0.0 {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "power".to_string(),
+                "has to be positive".to_string(),
+            )));
+        }
+        if self.eps < 0.0 {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "eps".to_string(),
+                "has to be non-negative".to_string(),
+            )));
+        }
+        let kernel = InverseDistance::new(power, self.eps).with_metric(metric);
+        match self.max_weight {
+            Some(max_weight) if max_weight > 0.0 => Ok(kernel.with_max_weight(max_weight)),
+            Some(_) => Err(Box::new(ConfigError::InvalidParameter(
+                "max_weight".to_string(),
+                "has to be positive".to_string(),
+            ))),
+            None if self.eps == 0.0 => Err(Box::new(ConfigError::InvalidParameter(
+                "eps".to_string(),
+                "has to be positive without `max_weight`, exact matches get infinite weight"
+                    .to_string(),
+            ))),
+            None => Ok(kernel),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct IndicatorBuilder {
+    mismatch: f32,
+}
+impl IndicatorBuilder {
+    pub fn build(self) -> Result<Indicator> {
+        if self.mismatch < 0.0 {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "mismatch".to_string(),
+                "has to be non-negative".to_string(),
+            )));
+        }
+        Ok(Indicator::new(self.mismatch))
     }
 }
 
 #[derive(Deserialize)]
 pub struct LeveledBuilder {
-    radius: f32,
+    radius: Option<f32>,
+    weights: Option<Vec<f32>>,
+    /// Not supported, the weights are the same for all prediction rows.
+    radius_col: Option<String>,
+    /// Not supported, as `radius_col`.
+    radius_table: Option<StrataBuilder>,
 }
 impl LeveledBuilder {
-    pub fn build(self, maxlvl: i32) -> Leveled {
-        Leveled::new(self.radius, maxlvl)
+    pub fn build(self, maxlvl: i32) -> Result<Leveled> {
+        reject_by_row("leveled", &self.radius_col, &self.radius_table)?;
+        match (self.radius, self.weights) {
+            (Some(radius), None) => Ok(Leveled::new(radius, maxlvl)),
+            (None, Some(weights)) => {
+                if weights.len() != maxlvl as usize && weights.len() != maxlvl as usize + 1 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "weights".to_string(),
+                        format!(
+                            "expected {} or {} weights for {} `coord` columns, got {}",
+                            maxlvl,
+                            maxlvl + 1,
+                            maxlvl,
+                            weights.len()
+                        ),
+                    )));
+                }
+                if weights.iter().any(|w| *w < 0.0) {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "weights".to_string(),
+                        "has to be non-negative".to_string(),
+                    )));
+                }
+                Ok(Leveled::from_weights(weights, maxlvl))
+            }
+            _ => Err(Box::new(ConfigError::InvalidParameter(
+                "radius".to_string(),
+                "exactly one of `radius` and `weights` has to be given".to_string(),
+            ))),
+        }
+    }
+
+    /// Leveled kernel on the distances in `table`, where distance `d` gets
+    /// `weights[d]` and distances beyond the weights get zero. The distances
+    /// have to be whole numbers, e.g. hops in an unweighted graph.
+    pub fn build_table(self, table: DistanceTable) -> Result<Leveled> {
+        reject_by_row("leveled", &self.radius_col, &self.radius_table)?;
+        if !table.is_integral() {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "distance".to_string(),
+                "leveled kernels require whole number `lookup` or `graph` distances".to_string(),
+            )));
+        }
+        let weights = self.weights.ok_or(ConfigError::InvalidParameter(
+            "weights".to_string(),
+            "leveled kernels on `lookup` or `graph` distance require `weights`".to_string(),
+        ))?;
+        if weights.is_empty() || weights.iter().any(|w| *w < 0.0) {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "weights".to_string(),
+                "has to be non-empty and non-negative".to_string(),
+            )));
+        }
+        let maxlvl = weights.len() as i32;
+        Ok(Leveled::from_weights(weights, maxlvl).with_table(Arc::new(table)))
+    }
+}
+
+/// Continuous kernel selected by its `kind` in a nested `kernel` table, e.g.
+/// `kernel = { kind = "Exponential", radius = 1.0 }`. `Sum`, `Product` and
+/// `Clamp` combine nested kernels, e.g. a floor weight for distant data with
+/// `kernel = { kind = "Sum", weights = [0.8], constant = 0.2, kernels = [
+/// { kind = "Exponential", radius = 1.0 }] }`.
+#[derive(Deserialize)]
+#[serde(tag = "kind")]
+pub enum KernelBuilder {
+    Exponential(ExponentialBuilder),
+    Tricubic(TricubicBuilder),
+    Gaussian(GaussianBuilder),
+    Epanechnikov(CompactBuilder),
+    Biweight(CompactBuilder),
+    Triweight(CompactBuilder),
+    Uniform(CompactBuilder),
+    Matern(MaternBuilder),
+    InverseDistance(InverseDistanceBuilder),
+    Sum {
+        kernels: Vec<KernelBuilder>,
+        weights: Vec<f32>,
+        #[serde(default)]
+        constant: f32,
+    },
+    Product {
+        kernels: Vec<KernelBuilder>,
+    },
+    Clamp {
+        kernel: Box<KernelBuilder>,
+        #[serde(default)]
+        floor: f32,
+        ceiling: Option<f32>,
+    },
+}
+impl KernelBuilder {
+    pub fn build(
+        self,
+        metric: Metric,
+        coord_data: &Matrix<f32>,
+        coord_pred: &Matrix<f32>,
+    ) -> Result<BoxedKernel> {
+        if self.by_row() {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "radius_col".to_string(),
+                "kernel parameters by prediction row are not supported in a nested `kernel`"
+                    .to_string(),
+            )));
+        }
+        let kernel: BoxedKernel = match self {
+            Self::Exponential(kernel) => Box::new(kernel.build(metric)?),
+            Self::Tricubic(kernel) => Box::new(kernel.build(metric, coord_data, coord_pred)?),
+            Self::Gaussian(kernel) => Box::new(kernel.build(metric)?),
+            Self::Epanechnikov(kernel) => {
+                Box::new(kernel.build(CompactShape::Epanechnikov, metric)?)
+            }
+            Self::Biweight(kernel) => Box::new(kernel.build(CompactShape::Biweight, metric)?),
+            Self::Triweight(kernel) => Box::new(kernel.build(CompactShape::Triweight, metric)?),
+            Self::Uniform(kernel) => Box::new(kernel.build(CompactShape::Uniform, metric)?),
+            Self::Matern(kernel) => Box::new(kernel.build(metric)?),
+            Self::InverseDistance(kernel) => Box::new(kernel.build(metric)?),
+            Self::Sum {
+                kernels,
+                weights,
+                constant,
+            } => {
+                if kernels.is_empty() || kernels.len() != weights.len() {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "weights".to_string(),
+                        "expected one weight for each of a non-empty list of `kernels`".to_string(),
+                    )));
+                }
+                if weights.iter().any(|w| *w < 0.0) {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "weights".to_string(),
+                        "has to be non-negative".to_string(),
+                    )));
+                }
+                if constant < 0.0 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "constant".to_string(),
+                        "has to be non-negative".to_string(),
+                    )));
+                }
+                let kernels = build_kernels(kernels, metric, coord_data, coord_pred)?;
+                Box::new(Sum::new(kernels, weights).with_constant(constant))
+            }
+            Self::Product { kernels } => {
+                if kernels.is_empty() {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "kernels".to_string(),
+                        "can't be empty".to_string(),
+                    )));
+                }
+                let kernels = build_kernels(kernels, metric, coord_data, coord_pred)?;
+                Box::new(Product::new(kernels))
+            }
+            Self::Clamp {
+                kernel,
+                floor,
+                ceiling,
+            } => {
+                let ceiling = ceiling.unwrap_or(f32::INFINITY);
+                if floor > ceiling {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "floor".to_string(),
+                        "can't exceed `ceiling`".to_string(),
+                    )));
+                }
+                let kernel = kernel.build(metric, coord_data, coord_pred)?;
+                Box::new(Clamp::new(kernel, floor, ceiling))
+            }
+        };
+        Ok(kernel)
+    }
+
+    /// Whether the kernel parameters are read by prediction row.
+    fn by_row(&self) -> bool {
+        match self {
+            Self::Exponential(kernel) => kernel.radius.by_row(),
+            Self::Tricubic(kernel) => kernel.by_row(),
+            Self::Gaussian(kernel) => kernel.radius.by_row(),
+            Self::Epanechnikov(kernel)
+            | Self::Biweight(kernel)
+            | Self::Triweight(kernel)
+            | Self::Uniform(kernel) => kernel.radius.by_row(),
+            Self::Matern(kernel) => kernel.radius.by_row(),
+            _ => false,
+        }
+    }
+}
+
+fn build_kernels(
+    kernels: Vec<KernelBuilder>,
+    metric: Metric,
+    coord_data: &Matrix<f32>,
+    coord_pred: &Matrix<f32>,
+) -> Result<Vec<BoxedKernel>> {
+    kernels
+        .into_iter()
+        .map(|kernel| kernel.build(metric.clone(), coord_data, coord_pred))
+        .collect()
+}
+
+/// Kernel `before` for data before the prediction point, i.e. at a smaller
+/// coordinate, and kernel `after` for data after it, e.g. `kernel = { before =
+/// { kind = "Tricubic", radius = 4.0, exponent = 0.5 }, causal = true }`. The
+/// sides are nested kernels like in `GenericComposite`. With `causal` data
+/// after the prediction point gets zero weight.
+#[derive(Deserialize)]
+pub struct AsymmetricBuilder {
+    before: KernelBuilder,
+    after: Option<KernelBuilder>,
+    #[serde(default)]
+    causal: bool,
+}
+impl AsymmetricBuilder {
+    pub fn build(self, coord_data: &Matrix<f32>, coord_pred: &Matrix<f32>) -> Result<Asymmetric> {
+        // the sides only see the distance to the prediction point
+        let before = self
+            .before
+            .build(Metric::Euclidean, coord_data, coord_pred)?;
+        let after = match (self.after, self.causal) {
+            (Some(after), false) => Some(after.build(Metric::Euclidean, coord_data, coord_pred)?),
+            (None, true) => None,
+            (Some(_), true) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "after".to_string(),
+                    "can't be used together with `causal`".to_string(),
+                )))
+            }
+            (None, false) => {
+                return Err(Box::new(ConfigError::InvalidParameter(
+                    "after".to_string(),
+                    "has to be given unless `causal`".to_string(),
+                )))
+            }
+        };
+        Ok(Asymmetric::new(before, after))
     }
 }
 
@@ -116,79 +1489,1120 @@ pub enum DimensionBuilder {
     GenericExponential {
         kernel: ExponentialBuilder,
         coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
     },
     GenericTricubic {
         kernel: TricubicBuilder,
         coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericGaussian {
+        kernel: GaussianBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericEpanechnikov {
+        kernel: CompactBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericBiweight {
+        kernel: CompactBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericTriweight {
+        kernel: CompactBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericUniform {
+        kernel: CompactBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericMatern {
+        kernel: MaternBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericInverseDistance {
+        kernel: InverseDistanceBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
     },
     GenericLeveled {
         kernel: LeveledBuilder,
         coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
     },
     CategoricalLeveled {
         kernel: LeveledBuilder,
         coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
     },
     AdaptiveTricubic {
-        kernel: TricubicBuilder,
+        kernel: AdaptiveTricubicBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    GenericIndicator {
+        kernel: IndicatorBuilder,
+        coord: Vec<String>,
+    },
+    /// Similarity on covariates, e.g. `coord = ["sdi", "income"]`. The columns
+    /// are standardized unless a `scale` is given, and weighted with the
+    /// continuous kernel in the nested `kernel` table.
+    Covariate {
+        kernel: KernelBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    /// Continuous kernel from a nested `kernel` table, e.g. a composite of
+    /// other kernels.
+    GenericComposite {
+        kernel: KernelBuilder,
+        coord: Vec<String>,
+        #[serde(flatten)]
+        metric: MetricBuilder,
+    },
+    /// Kernel on the signed difference of a single coordinate, e.g. for
+    /// weighting past years differently from future years.
+    GenericAsymmetric {
+        kernel: AsymmetricBuilder,
         coord: Vec<String>,
     },
 }
 
 impl DimensionBuilder {
+    /// Build the dimension, along with a label of the kernel parameters of
+    /// each prediction row when they vary by row.
     pub fn build(
         self,
         data_reader: &ParquetFileReader,
         pred_reader: &ParquetFileReader,
-    ) -> Dimension {
-        match self {
-            Self::GenericExponential { kernel, coord } => {
-                let coord_data = data_reader.read_cols::<f32>(&coord).unwrap();
-                let coord_pred = pred_reader.read_cols::<f32>(&coord).unwrap();
-                let kernel = kernel.build();
-                Dimension::GenericExponential(DimensionHandle::new(kernel, coord_data, coord_pred))
-            }
-            Self::GenericTricubic { kernel, coord } => {
-                let coord_data = data_reader.read_cols::<f32>(&coord).unwrap();
-                let coord_pred = pred_reader.read_cols::<f32>(&coord).unwrap();
-                let kernel = kernel.build(&coord_data, &coord_pred);
-                Dimension::GenericTricubic(DimensionHandle::new(kernel, coord_data, coord_pred))
-            }
-            Self::GenericLeveled { kernel, coord } => {
-                let coord_data = data_reader.read_cols::<i32>(&coord).unwrap();
-                let coord_pred = pred_reader.read_cols::<i32>(&coord).unwrap();
-                let kernel = kernel.build(coord_data.ncols as i32);
+    ) -> Result<(Dimension, Option<Vec<String>>)> {
+        let mut labels = None;
+        let dimension = match self {
+            Self::GenericExponential {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericExponential(handle)
+            }
+            Self::GenericTricubic {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (metric, coord_data, coord_pred) =
+                    metric.build_coords(&coord, data_reader, pred_reader)?;
+                let tricubic = kernel.build(metric, &coord_data, &coord_pred)?;
+                let pred_kernels = kernel.read(&tricubic, pred_reader)?;
+                if let Some(kernels) = &pred_kernels {
+                    let radius: Vec<f32> = kernels.iter().map(|k| k.radius).collect();
+                    let exponent: Vec<f32> = kernels.iter().map(|k| k.exponent).collect();
+                    let data_min_max = partialord_min_max(&coord_data);
+                    check_support(&radius, &tricubic.metric, &data_min_max, &coord_pred);
+                    labels = Some(param_labels(
+                        &coord,
+                        &[("radius", &radius), ("exponent", &exponent)],
+                    ));
+                }
+                let handle = DimensionHandle::new(tricubic, coord_data, coord_pred);
+                Dimension::GenericTricubic(match pred_kernels {
+                    Some(kernels) => handle.with_pred_kernels(kernels)?,
+                    None => handle,
+                })
+            }
+            Self::GenericGaussian {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericGaussian(handle)
+            }
+            Self::GenericEpanechnikov {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(CompactShape::Epanechnikov, metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericEpanechnikov(handle)
+            }
+            Self::GenericBiweight {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(CompactShape::Biweight, metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericBiweight(handle)
+            }
+            Self::GenericTriweight {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(CompactShape::Triweight, metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericTriweight(handle)
+            }
+            Self::GenericUniform {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(CompactShape::Uniform, metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericUniform(handle)
+            }
+            Self::GenericMatern {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let handle;
+                (handle, labels) = build_rescaled(
+                    &coord,
+                    metric,
+                    &kernel.radius,
+                    |metric| kernel.build(metric),
+                    data_reader,
+                    pred_reader,
+                )?;
+                Dimension::GenericMatern(handle)
+            }
+            Self::GenericInverseDistance {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (metric, coord_data, coord_pred) =
+                    metric.build_coords(&coord, data_reader, pred_reader)?;
+                let kernel = kernel.build(metric)?;
+                Dimension::GenericInverseDistance(DimensionHandle::new(
+                    kernel, coord_data, coord_pred,
+                ))
+            }
+            Self::GenericLeveled {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (table, coord_data, coord_pred) =
+                    metric.build_levels(&coord, data_reader, pred_reader)?;
+                let kernel = match table {
+                    Some(table) => kernel.build_table(table)?,
+                    None => kernel.build(coord_data.ncols as i32)?,
+                };
                 Dimension::GenericLeveled(DimensionHandle::new(kernel, coord_data, coord_pred))
             }
-            Self::CategoricalLeveled { kernel, coord } => {
-                let coord_data = data_reader.read_cols::<i32>(&coord).unwrap();
-                let coord_pred = pred_reader.read_cols::<i32>(&coord).unwrap();
-                let kernel = kernel.build(coord_data.ncols as i32);
+            Self::CategoricalLeveled {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (table, coord_data, coord_pred) =
+                    metric.build_levels(&coord, data_reader, pred_reader)?;
+                let kernel = match table {
+                    Some(table) => kernel.build_table(table)?,
+                    None => kernel.build(coord_data.ncols as i32)?,
+                };
                 Dimension::CategoricalLeveled(DimensionHandle::new(kernel, coord_data, coord_pred))
             }
-            Self::AdaptiveTricubic { kernel, coord } => {
-                let coord_data = data_reader.read_cols::<f32>(&coord).unwrap();
-                let coord_pred = pred_reader.read_cols::<f32>(&coord).unwrap();
-                let kernel = kernel.build(&coord_data, &coord_pred);
+            Self::AdaptiveTricubic {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (metric, coord_data, coord_pred) =
+                    metric.build_coords(&coord, data_reader, pred_reader)?;
+                let kernel = kernel.build(metric)?;
                 Dimension::AdaptiveTricubic(DimensionHandle::new(kernel, coord_data, coord_pred))
             }
-        }
+            Self::GenericIndicator { kernel, coord } => {
+                let (coord_data, coord_pred) = read_levels(data_reader, pred_reader, &coord, None)?;
+                let kernel = kernel.build()?;
+                Dimension::GenericIndicator(DimensionHandle::new(kernel, coord_data, coord_pred))
+            }
+            Self::Covariate {
+                kernel,
+                coord,
+                mut metric,
+            } => {
+                // mahalanobis distance accounts for the scales itself
+                metric.standardize |=
+                    metric.scale.is_none() && metric.distance != Some(DistanceKind::Mahalanobis);
+                let (metric, coord_data, coord_pred) =
+                    metric.build_coords(&coord, data_reader, pred_reader)?;
+                let kernel = kernel.build(metric, &coord_data, &coord_pred)?;
+                Dimension::Covariate(DimensionHandle::new(kernel, coord_data, coord_pred))
+            }
+            Self::GenericComposite {
+                kernel,
+                coord,
+                metric,
+            } => {
+                let (metric, coord_data, coord_pred) =
+                    metric.build_coords(&coord, data_reader, pred_reader)?;
+                let kernel = kernel.build(metric, &coord_data, &coord_pred)?;
+                Dimension::GenericComposite(DimensionHandle::new(kernel, coord_data, coord_pred))
+            }
+            Self::GenericAsymmetric { kernel, coord } => {
+                if coord.len() != 1 {
+                    return Err(Box::new(ConfigError::InvalidParameter(
+                        "coord".to_string(),
+                        "asymmetric kernels require a single column".to_string(),
+                    )));
+                }
+                let coord_data = data_reader.read_float_cols(&coord)?;
+                let coord_pred = pred_reader.read_float_cols(&coord)?;
+                let kernel = kernel.build(&coord_data, &coord_pred)?;
+                Dimension::GenericAsymmetric(DimensionHandle::new(kernel, coord_data, coord_pred))
+            }
+        };
+        Ok((dimension, labels))
     }
 }
 
-fn partialord_min_max<T: PartialOrd>(coords: &Matrix<T>) -> (&T, &T) {
-    let mut coords_iter = coords.vec.iter();
-    let first = coords_iter.next().unwrap();
-    let mut min_max = (first, first);
+/// Dimension of a kernel that `build` makes from the metric, rescaled by
+/// prediction row when the radius is read by row.
+fn build_rescaled<K: Rescale<CType = f32>>(
+    coord: &[String],
+    metric: MetricBuilder,
+    radius: &RadiusBuilder,
+    build: impl FnOnce(Metric) -> Result<K>,
+    data_reader: &ParquetFileReader,
+    pred_reader: &ParquetFileReader,
+) -> Result<(DimensionHandle<K>, Option<Vec<String>>)> {
+    let (metric, coord_data, coord_pred) = metric.build_coords(coord, data_reader, pred_reader)?;
+    let radius = radius.read(pred_reader)?;
+    let labels = radius
+        .as_deref()
+        .map(|radius| param_labels(coord, &[("radius", radius)]));
+    let handle = DimensionHandle::new(build(metric)?, coord_data, coord_pred);
+    Ok((handle.with_pred_radius(radius)?, labels))
+}
 
-    coords_iter.for_each(|x| {
-        if x < min_max.0 {
-            min_max.0 = x;
-        }
-        if x > min_max.1 {
-            min_max.1 = x;
-        }
+fn partialord_min_max<T: PartialOrd>(coords: &Matrix<T>) -> Vec<(&T, &T)> {
+    let mut rows = coords.rows();
+    let mut min_max: Vec<(&T, &T)> = rows.next().unwrap().iter().map(|x| (x, x)).collect();
+
+    rows.for_each(|row| {
+        row.iter()
+            .zip(min_max.iter_mut())
+            .for_each(|(x, (min, max))| {
+                if x < *min {
+                    *min = x;
+                }
+                if x > *max {
+                    *max = x;
+                }
+            })
     });
 
     min_max
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        data::io::{temp_path, write_parquet_col, write_test_cols, TestCol},
+        model::kernel::Kernel,
+    };
+
+    fn parse_dimension(s: &str) -> DimensionBuilder {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_metric_builder() {
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericExponential"
+            coord = ["age_mid", "year_id"]
+            kernel = { radius = 1.0 }
+            distance = "minkowski"
+            p = 3.0
+            "#,
+        );
+        let DimensionBuilder::GenericExponential { metric, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        assert_eq!(metric.build().unwrap(), Metric::Minkowski(3.0));
+
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericTricubic"
+            coord = ["year_id"]
+            kernel = { exponent = 0.5 }
+            "#,
+        );
+        let DimensionBuilder::GenericTricubic { metric, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        assert_eq!(metric.build().unwrap(), Metric::Euclidean);
+
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericExponential"
+            coord = ["lat", "lon"]
+            kernel = { radius = 500.0 }
+            distance = "haversine"
+            "#,
+        );
+        let DimensionBuilder::GenericExponential { metric, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        assert_eq!(metric.build().unwrap(), Metric::Haversine);
+    }
+
+    #[test]
+    fn test_covariate_builder() {
+        let dim = parse_dimension(
+            r#"
+            kind = "Covariate"
+            coord = ["sdi", "income"]
+            kernel = { kind = "Gaussian", radius = 1.0 }
+            "#,
+        );
+        let DimensionBuilder::Covariate { kernel, metric, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        assert_eq!(metric.build().unwrap(), Metric::Euclidean);
+
+        let coords = Matrix::new(vec![0.0, 0.0, 2.0, 0.0], 2);
+        let kernel = kernel.build(Metric::Euclidean, &coords, &coords).unwrap();
+        assert_eq!(kernel.kernel(&[0.0, 0.0], &[2.0, 0.0]), (-2.0_f32).exp());
+    }
+
+    #[test]
+    fn test_composite_builder() {
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericComposite"
+            coord = ["year_id"]
+            [kernel]
+            kind = "Clamp"
+            ceiling = 0.9
+            kernel = { kind = "Sum", weights = [0.8], constant = 0.2, kernels = [
+                { kind = "Exponential", radius = 1.0 },
+            ] }
+            "#,
+        );
+        let DimensionBuilder::GenericComposite { kernel, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        let coords = Matrix::new(vec![0.0, 1.0], 1);
+        let kernel = kernel.build(Metric::Euclidean, &coords, &coords).unwrap();
+        assert_eq!(kernel.kernel(&[0.0], &[0.0]), 0.9);
+        assert_eq!(kernel.kernel(&[0.0], &[1.0]), 0.8 * (-1.0_f32).exp() + 0.2);
+
+        let kernel: KernelBuilder = toml::from_str(
+            r#"
+            kind = "Sum"
+            weights = [0.5, 0.5]
+            kernels = [{ kind = "Gaussian", radius = 1.0 }]
+            "#,
+        )
+        .unwrap();
+        assert!(kernel.build(Metric::Euclidean, &coords, &coords).is_err());
+
+        let kernel: KernelBuilder = toml::from_str(
+            r#"
+            kind = "Sum"
+            weights = [-0.5]
+            kernels = [{ kind = "Gaussian", radius = 1.0 }]
+            "#,
+        )
+        .unwrap();
+        assert!(kernel.build(Metric::Euclidean, &coords, &coords).is_err());
+
+        let kernel: KernelBuilder = toml::from_str(
+            r#"
+            kind = "Sum"
+            weights = [0.5]
+            constant = -0.1
+            kernels = [{ kind = "Gaussian", radius = 1.0 }]
+            "#,
+        )
+        .unwrap();
+        assert!(kernel.build(Metric::Euclidean, &coords, &coords).is_err());
+    }
+
+    #[test]
+    fn test_matern_builder() {
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericMatern"
+            coord = ["lat", "lon"]
+            kernel = { radius = 2.0, nu = 1.5 }
+            distance = "haversine"
+            "#,
+        );
+        let DimensionBuilder::GenericMatern { kernel, metric, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        let kernel = kernel.build(metric.build().unwrap()).unwrap();
+        assert_eq!(kernel.nu, MaternSmoothness::ThreeHalves);
+        assert_eq!(kernel.metric, Metric::Haversine);
+
+        let builder: MaternBuilder = toml::from_str("radius = 2.0\nnu = 1.0").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_inverse_distance_builder() {
+        let builder: InverseDistanceBuilder = toml::from_str("power = 1.0").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_err());
+
+        let builder: InverseDistanceBuilder = toml::from_str("max_weight = 4.0").unwrap();
+        let kernel = builder.build(Metric::Euclidean).unwrap();
+        assert_eq!(kernel.kernel(&[1.0], &[1.0]), 4.0);
+        assert_eq!(kernel.kernel(&[1.0], &[3.0]), 0.25);
+
+        let builder: InverseDistanceBuilder = toml::from_str("eps = 1.0\npower = 0.0").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_err());
+    }
+
+    #[test]
+    fn test_radius_builder() {
+        let dim = parse_dimension(
+            r#"
+            kind = "GenericGaussian"
+            coord = ["year_id"]
+            kernel = { radius_col = "time_radius" }
+            "#,
+        );
+        let DimensionBuilder::GenericGaussian { kernel, .. } = dim else {
+            panic!("wrong dimension kind");
+        };
+        assert_eq!(kernel.radius.radius_col.as_deref(), Some("time_radius"));
+        assert!(kernel.build(Metric::Euclidean).is_ok());
+
+        let builder: CompactBuilder = toml::from_str("radius = 1.0\nradius_col = \"r\"").unwrap();
+        assert!(builder
+            .build(CompactShape::Uniform, Metric::Euclidean)
+            .is_err());
+
+        let kernel: KernelBuilder =
+            toml::from_str("kind = \"Exponential\"\nradius_col = \"r\"").unwrap();
+        let coords = Matrix::new(vec![0.0], 1);
+        assert!(kernel.build(Metric::Euclidean, &coords, &coords).is_err());
+
+        let builder: TricubicBuilder = toml::from_str("radius_col = \"r\"").unwrap();
+        assert!(builder.build(Metric::Euclidean, &coords, &coords).is_err());
+    }
+
+    #[test]
+    fn test_strata_builder() {
+        let path = temp_path("strata");
+        write_parquet_col(&path, "age_group_id", &[1_i32, 2, 1]).unwrap();
+        let pred_reader = ParquetFileReader::new(&path).unwrap();
+
+        let table: StrataBuilder =
+            toml::from_str("by = \"age_group_id\"\nstrata = [1, 2]\nvalues = [5.0, 2.0]").unwrap();
+        let radius = table.join("radius", &pred_reader).unwrap();
+        assert_eq!(radius, vec![5.0, 2.0, 5.0]);
+
+        let labels = param_labels(&["year_id".to_string()], &[("radius", &radius)]);
+        assert_eq!(labels[1], "year_id: radius=2");
+
+        let table: StrataBuilder =
+            toml::from_str("by = \"age_group_id\"\nstrata = [1]\nvalues = [5.0]").unwrap();
+        assert!(table.join("radius", &pred_reader).is_err());
+
+        // table file with an `INT64` stratum column
+        let table_path = temp_path("strata_table");
+        write_test_cols(
+            &table_path,
+            &[
+                ("age_group_id", TestCol::Long(vec![Some(2), Some(1)])),
+                ("radius", TestCol::Double(vec![Some(3.0), Some(4.0)])),
+            ],
+        );
+        let table: StrataBuilder =
+            toml::from_str(&format!("by = \"age_group_id\"\npath = \"{}\"", table_path)).unwrap();
+        assert_eq!(
+            table.join("radius", &pred_reader).unwrap(),
+            vec![4.0, 3.0, 4.0]
+        );
+        assert!(table.join("exponent", &pred_reader).is_err());
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&table_path).unwrap();
+    }
+
+    #[test]
+    fn test_asymmetric_builder() {
+        let coords = Matrix::new(vec![0.0, 1.0], 1);
+        let builder: AsymmetricBuilder =
+            toml::from_str("before = { kind = \"Tricubic\", radius = 4.0 }\ncausal = true")
+                .unwrap();
+        assert!(builder.build(&coords, &coords).is_err());
+
+        let builder: AsymmetricBuilder = toml::from_str(
+            "before = { kind = \"Tricubic\", radius = 4.0, exponent = 0.5 }\ncausal = true",
+        )
+        .unwrap();
+        let kernel = builder.build(&coords, &coords).unwrap();
+        assert_eq!(kernel.kernel_from_distance(&1.0), 0.125);
+        assert_eq!(kernel.kernel_from_distance(&-1.0), 0.0);
+
+        let builder: AsymmetricBuilder = toml::from_str(
+            "before = { kind = \"Uniform\", radius = 1.0 }\n\
+             after = { kind = \"Exponential\", radius = 2.0 }",
+        )
+        .unwrap();
+        let kernel = builder.build(&coords, &coords).unwrap();
+        assert_eq!(kernel.kernel_from_distance(&0.5), 1.0);
+        assert_eq!(kernel.kernel_from_distance(&-2.0), (-1.0_f32).exp());
+
+        let builder: AsymmetricBuilder =
+            toml::from_str("before = { kind = \"Uniform\", radius = 1.0 }").unwrap();
+        assert!(builder.build(&coords, &coords).is_err());
+
+        let builder: AsymmetricBuilder =
+            toml::from_str("before = { kind = \"Uniform\", radius = 0.0 }\ncausal = true").unwrap();
+        assert!(builder.build(&coords, &coords).is_err());
+
+        let builder: AsymmetricBuilder = toml::from_str(
+            "before = { kind = \"Uniform\", radius_col = \"radius\" }\ncausal = true",
+        )
+        .unwrap();
+        assert!(builder.build(&coords, &coords).is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let coords = Matrix::new(vec![0.0, 5.0, 2.0, 5.0, 4.0, 5.0], 2);
+        let scale = col_std(&coords);
+        let ok_scale = vec![(8.0_f32 / 3.0).sqrt(), 1.0];
+        assert_eq!(scale, ok_scale);
+
+        assert_eq!(col_cov(&coords), vec![8.0 / 3.0, 0.0, 0.0, 0.0]);
+
+        let coords = scale_cols(coords, &[2.0, 5.0]);
+        assert_eq!(coords.vec, vec![0.0, 1.0, 1.0, 1.0, 2.0, 1.0]);
+    }
+
+    #[test]
+    fn test_scaled_coords() {
+        let (data, pred) = (temp_path("scaled_data"), temp_path("scaled_pred"));
+        write_test_cols(
+            &data,
+            &[
+                ("x", TestCol::Float(vec![Some(0.0), Some(2.0), Some(4.0)])),
+                ("y", TestCol::Double(vec![Some(5.0), Some(5.0), Some(5.0)])),
+            ],
+        );
+        write_test_cols(
+            &pred,
+            &[
+                ("x", TestCol::Float(vec![Some(8.0)])),
+                ("y", TestCol::Double(vec![Some(10.0)])),
+            ],
+        );
+        let data_reader = ParquetFileReader::new(&data).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+        let coord = ["x".to_string(), "y".to_string()];
+        let build = |config: &str| {
+            let metric: MetricBuilder = toml::from_str(config).unwrap();
+            metric.build_coords(&coord, &data_reader, &pred_reader)
+        };
+
+        // by the standard deviations of the data, a constant column isn't
+        // scaled
+        let (metric, coord_data, coord_pred) = build("standardize = true").unwrap();
+        let std = (8.0_f32 / 3.0).sqrt();
+        assert_eq!(metric, Metric::Euclidean);
+        assert_eq!(
+            coord_data.vec,
+            vec![0.0, 5.0, 2.0 / std, 5.0, 4.0 / std, 5.0]
+        );
+        assert_eq!(coord_pred.vec, vec![8.0 / std, 10.0]);
+
+        let (_, coord_data, coord_pred) = build("scale = [2.0, 5.0]").unwrap();
+        assert_eq!(coord_data.vec, vec![0.0, 1.0, 1.0, 1.0, 2.0, 1.0]);
+        assert_eq!(coord_pred.vec, vec![4.0, 2.0]);
+
+        for config in ["scale = [2.0]", "scale = [2.0, 0.0]"] {
+            let err = build(config).err().unwrap();
+            assert!(err
+                .to_string()
+                .contains("one positive value per `coord` column"));
+        }
+        let err = build("scale = [2.0, 5.0]\nstandardize = true")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("together with `standardize`"));
+
+        fs::remove_file(&data).unwrap();
+        fs::remove_file(&pred).unwrap();
+    }
+
+    #[test]
+    fn test_mahalanobis_builder() {
+        let path = temp_path("mahalanobis");
+        write_test_cols(
+            &path,
+            &[
+                (
+                    "x",
+                    TestCol::Float(vec![Some(0.0), Some(1.0), Some(2.0), Some(3.0)]),
+                ),
+                (
+                    "y",
+                    TestCol::Float(vec![Some(0.0), Some(2.0), Some(1.0), Some(3.0)]),
+                ),
+                (
+                    "z",
+                    TestCol::Float(vec![Some(0.0), Some(2.0), Some(4.0), Some(6.0)]),
+                ),
+            ],
+        );
+        let reader = ParquetFileReader::new(&path).unwrap();
+        let build = |config: &str, coord: &[&str]| {
+            let metric: MetricBuilder =
+                toml::from_str(&format!("distance = \"mahalanobis\"\n{}", config)).unwrap();
+            let coord: Vec<String> = coord.iter().map(|c| c.to_string()).collect();
+            metric
+                .build_coords(&coord, &reader, &reader)
+                .map(|(metric, _, _)| metric)
+        };
+
+        let metric = build("covariance = [[2.0, 0.0], [0.0, 0.5]]", &["x", "y"]).unwrap();
+        assert_eq!(
+            metric,
+            Metric::Mahalanobis(Arc::new(vec![0.5, 0.0, 0.0, 2.0]))
+        );
+
+        // estimated from the data, the columns aren't scaled
+        let metric = build("", &["x", "y"]).unwrap();
+        let precision = invert(&[1.25, 1.0, 1.0, 1.25], 2).unwrap();
+        assert_eq!(metric, Metric::Mahalanobis(Arc::new(precision)));
+
+        for config in ["covariance = [[1.0, 0.0]]", "covariance = [[1.0], [0.0]]"] {
+            let err = build(config, &["x", "y"]).err().unwrap();
+            assert!(err.to_string().contains("has to be 2 by 2"));
+        }
+
+        // `z` is twice `x`
+        let err = build("", &["x", "z"]).err().unwrap();
+        assert!(err.to_string().contains("is singular"));
+        let err = build("covariance = [[1.0, 1.0], [1.0, 1.0]]", &["x", "y"])
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("is singular"));
+
+        for config in ["scale = [1.0, 1.0]", "standardize = true"] {
+            let err = build(config, &["x", "y"]).err().unwrap();
+            assert!(matches!(
+                err.downcast_ref::<ConfigError>(),
+                Some(ConfigError::UnsupportedDistance(..))
+            ));
+        }
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_adaptive_tricubic_builder() {
+        let builder: AdaptiveTricubicBuilder = toml::from_str("exponent = 3.0\nk = 1").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_ok());
+
+        let builder: AdaptiveTricubicBuilder =
+            toml::from_str("exponent = 3.0\nradius = 2.0").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_err());
+
+        let builder: AdaptiveTricubicBuilder =
+            toml::from_str("exponent = 3.0\nradius_col = \"radius\"").unwrap();
+        assert!(builder.build(Metric::Euclidean).is_err());
+
+        let builder: AdaptiveTricubicBuilder = toml::from_str("exponent = 3.0\nk = 1").unwrap();
+        let table = Metric::Table(Arc::new(DistanceTable::new(1)));
+        assert!(builder.build(table).is_err());
+    }
+
+    #[test]
+    fn test_leveled_builder() {
+        let builder: LeveledBuilder = toml::from_str("weights = [1.0, 0.6, 0.2, 0.05]").unwrap();
+        assert_eq!(builder.build(3).unwrap().weights, vec![1.0, 0.6, 0.2, 0.05]);
+
+        let builder: LeveledBuilder = toml::from_str("weights = [1.0, 0.6]").unwrap();
+        assert!(builder.build(3).is_err());
+
+        let builder: LeveledBuilder = toml::from_str("radius = 0.5\nweights = [1.0]").unwrap();
+        assert!(builder.build(1).is_err());
+
+        let builder: LeveledBuilder =
+            toml::from_str("radius = 0.5\nradius_col = \"radius\"").unwrap();
+        assert!(builder.build(1).is_err());
+
+        // fractional table distances would be truncated to a level
+        let builder: LeveledBuilder = toml::from_str("weights = [1.0, 0.5]").unwrap();
+        let table = DistanceTable::from_graph(2, 2, &[(0, 1, 0.5)], &[0]);
+        assert!(builder.build_table(table).is_err());
+    }
+
+    #[test]
+    fn test_weave_by() {
+        let (data, pred) = (temp_path("by_data"), temp_path("by_pred"));
+        let config = format!(
+            "[input.data]\npath = \"{}\"\nvalues = \"value\"\n\
+             [input.pred]\npath = \"{}\"\n\
+             [output]\npath = \"out.parquet\"\nvalues = \"prediction\"\n\
+             [[dimensions]]\nkind = \"GenericExponential\"\ncoord = [\"age\"]\n\
+             kernel = {{ radius = 1.0 }}\n",
+            data, pred
+        );
+        // `by` against an indicator with no weight on a mismatch
+        let weave = |by: &str| {
+            let grouped: WeaveBuilder =
+                toml::from_str(&format!("by = [\"{}\"]\n{}", by, config)).unwrap();
+            let indicator: WeaveBuilder = toml::from_str(&format!(
+                "{}[[dimensions]]\nkind = \"GenericIndicator\"\ncoord = [\"{}\"]\n\
+                 kernel = {{ mismatch = 0.0 }}",
+                config, by
+            ))
+            .unwrap();
+            (grouped.build().unwrap(), indicator.build().unwrap())
+        };
+
+        // `INT64` and `UTF8` columns are encoded as strings, the last data
+        // and prediction rows have a missing `by`
+        write_test_cols(
+            &data,
+            &[
+                (
+                    "age",
+                    TestCol::Float(vec![Some(0.0), Some(1.0), Some(0.0), Some(1.0), Some(0.5)]),
+                ),
+                (
+                    "value",
+                    TestCol::Float(vec![Some(1.0), Some(2.0), Some(3.0), Some(4.0), Some(5.0)]),
+                ),
+                (
+                    "sex",
+                    TestCol::Long(vec![Some(1), Some(1), Some(2), Some(2), None]),
+                ),
+                (
+                    "region",
+                    TestCol::Str(vec![Some("a"), Some("a"), Some("b"), Some("b"), None]),
+                ),
+            ],
+        );
+        write_test_cols(
+            &pred,
+            &[
+                ("age", TestCol::Float(vec![Some(0.0), Some(1.0), Some(0.5)])),
+                ("sex", TestCol::Long(vec![Some(1), Some(2), None])),
+                ("region", TestCol::Str(vec![Some("a"), Some("b"), None])),
+            ],
+        );
+        for by in ["sex", "region"] {
+            let (grouped, indicator) = weave(by);
+            for i in 0..2 {
+                assert!((grouped.avg_for(i) - indicator.avg_for(i)).abs() < 1e-6);
+            }
+            assert!(grouped.avg_for(0) < 2.0 && grouped.avg_for(1) > 3.0);
+            assert!(grouped.avg_for(2).is_nan());
+        }
+
+        fs::remove_file(&data).unwrap();
+        fs::remove_file(&pred).unwrap();
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let (data, pred, hierarchy) = (
+            temp_path("hierarchy_data"),
+            temp_path("hierarchy_pred"),
+            temp_path("hierarchy"),
+        );
+        write_parquet_col(&data, "loc", &[3_i32, 4]).unwrap();
+        write_parquet_col(&pred, "loc", &[1_i32]).unwrap();
+        // 1 - 2 - 3
+        // 4
+        write_test_cols(
+            &hierarchy,
+            &[
+                (
+                    "loc",
+                    TestCol::Int(vec![Some(1), Some(2), Some(3), Some(4)]),
+                ),
+                (
+                    "parent_id",
+                    TestCol::Int(vec![None, Some(1), Some(2), None]),
+                ),
+            ],
+        );
+        let data_reader = ParquetFileReader::new(&data).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+        let metric: MetricBuilder =
+            toml::from_str(&format!("hierarchy = \"{}\"", hierarchy)).unwrap();
+        let (table, levels_data, levels_pred) = metric
+            .build_levels(&["loc".to_string()], &data_reader, &pred_reader)
+            .unwrap();
+        assert!(table.is_none());
+        assert_eq!(levels_data.vec, vec![1, 2, 3, 4, 4, 4]);
+        assert_eq!(levels_pred.vec, vec![1, 1, 1]);
+
+        for path in [data, pred, hierarchy] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lookup_table() {
+        let (data, pred, table) = (
+            temp_path("lookup_data"),
+            temp_path("lookup_pred"),
+            temp_path("lookup_table"),
+        );
+        write_parquet_col(&data, "loc", &[1_i32, 2]).unwrap();
+        write_parquet_col(&pred, "loc", &[2_i32]).unwrap();
+        let data_reader = ParquetFileReader::new(&data).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+        let coord = ["loc".to_string()];
+        let metric: MetricBuilder =
+            toml::from_str(&format!("distance = \"lookup\"\ntable = \"{}\"", table)).unwrap();
+        let write_table = |distance: f64| {
+            write_test_cols(
+                &table,
+                &[
+                    ("id_a", TestCol::Int(vec![Some(1)])),
+                    ("id_b", TestCol::Int(vec![Some(2)])),
+                    ("distance", TestCol::Double(vec![Some(distance)])),
+                ],
+            )
+        };
+
+        write_table(1.5);
+        let (distance, codes_data, codes_pred) = metric
+            .build_table(&coord, &data_reader, &pred_reader)
+            .unwrap()
+            .unwrap();
+        let my_distance: Vec<f32> = codes_data
+            .vec
+            .iter()
+            .map(|j| distance.get(codes_pred.vec[0], *j))
+            .collect();
+        assert_eq!(my_distance, vec![1.5, 0.0]);
+
+        write_table(-1.0);
+        assert!(metric
+            .build_table(&coord, &data_reader, &pred_reader)
+            .is_err());
+
+        // no distance between the prediction id 3 and the data ids
+        write_table(1.5);
+        write_parquet_col(&pred, "loc", &[3_i32]).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+        let err = metric
+            .build_table(&coord, &data_reader, &pred_reader)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.downcast_ref::<ConfigError>(),
+            Some(ConfigError::MissingDistance(..))
+        ));
+
+        for path in [data, pred, table] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_graph_table() {
+        let (data, pred, graph) = (
+            temp_path("graph_data"),
+            temp_path("graph_pred"),
+            temp_path("graph_edges"),
+        );
+        write_parquet_col(&data, "loc", &[1_i32, 2, 3]).unwrap();
+        write_parquet_col(&pred, "loc", &[1_i32]).unwrap();
+        let data_reader = ParquetFileReader::new(&data).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+        let coord = ["loc".to_string()];
+        let distance = |metric: &str, weights: Option<(&str, Vec<f64>)>| {
+            let mut cols = vec![
+                ("id_a", TestCol::Int(vec![Some(1), Some(2)])),
+                ("id_b", TestCol::Int(vec![Some(2), Some(3)])),
+            ];
+            if let Some((name, weights)) = weights {
+                cols.push((
+                    name,
+                    TestCol::Double(weights.into_iter().map(Some).collect()),
+                ));
+            }
+            write_test_cols(&graph, &cols);
+            let metric: MetricBuilder = toml::from_str(&format!(
+                "distance = \"graph\"\ngraph = \"{}\"\n{}",
+                graph, metric
+            ))
+            .unwrap();
+            let (table, codes_data, codes_pred) = metric
+                .build_table(&coord, &data_reader, &pred_reader)?
+                .unwrap();
+            Ok::<Vec<f32>, Box<dyn std::error::Error>>(
+                codes_data
+                    .vec
+                    .iter()
+                    .map(|j| table.get(codes_pred.vec[0], *j))
+                    .collect(),
+            )
+        };
+
+        // hops without a weight column, the `weight` column by default
+        assert_eq!(distance("", None).unwrap(), vec![0.0, 1.0, 2.0]);
+        let weights = Some(("weight", vec![0.5, 2.0]));
+        assert_eq!(distance("", weights).unwrap(), vec![0.0, 0.5, 2.5]);
+        let weights = Some(("length", vec![0.5, 2.0]));
+        assert_eq!(distance("", weights.clone()).unwrap(), vec![0.0, 1.0, 2.0]);
+        let metric = "weight_col = \"length\"";
+        assert_eq!(distance(metric, weights).unwrap(), vec![0.0, 0.5, 2.5]);
+        assert!(distance(metric, None).is_err());
+        assert!(distance("", Some(("weight", vec![0.5, -2.0]))).is_err());
+
+        for path in [data, pred, graph] {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_read_levels() {
+        let (data, pred) = (temp_path("levels_data"), temp_path("levels_pred"));
+        write_test_cols(
+            &data,
+            &[
+                ("region", TestCol::Str(vec![Some("a"), Some("b"), None])),
+                ("loc", TestCol::Long(vec![Some(7), Some(-1), Some(8)])),
+            ],
+        );
+        write_test_cols(
+            &pred,
+            &[
+                ("region", TestCol::Str(vec![Some("b"), Some("c")])),
+                ("loc", TestCol::Long(vec![Some(-1), Some(7)])),
+            ],
+        );
+        let data_reader = ParquetFileReader::new(&data).unwrap();
+        let pred_reader = ParquetFileReader::new(&pred).unwrap();
+
+        // `UTF8` levels share their codes between the files, nulls are missing
+        let coord = ["region".to_string()];
+        let (levels_data, levels_pred) =
+            read_levels(&data_reader, &pred_reader, &coord, None).unwrap();
+        assert_eq!(levels_data.vec, vec![0, 1, MISSING_LEVEL]);
+        assert_eq!(levels_pred.vec, vec![1, 2]);
+
+        // `INT64` levels equal to `missing` are missing
+        let coord = ["loc".to_string()];
+        let (levels_data, levels_pred) =
+            read_levels(&data_reader, &pred_reader, &coord, Some(-1)).unwrap();
+        assert_eq!(levels_data.vec, vec![0, MISSING_LEVEL, 1]);
+        assert_eq!(levels_pred.vec, vec![MISSING_LEVEL, 0]);
+
+        fs::remove_file(&data).unwrap();
+        fs::remove_file(&pred).unwrap();
+    }
+
+    #[test]
+    fn test_check_allow_negative() {
+        let table = Metric::Table(Arc::new(DistanceTable::new(1)));
+        assert!(check_allow_negative(true, &table).is_err());
+        assert!(check_allow_negative(false, &table).is_ok());
+        assert!(check_allow_negative(true, &Metric::Euclidean).is_ok());
+    }
+
+    #[test]
+    fn test_metric_builder_errors() {
+        let metric: MetricBuilder = toml::from_str(r#"distance = "minkowski""#).unwrap();
+        assert!(metric.build().is_err());
+
+        let metric: MetricBuilder = toml::from_str(r#"distance = "cyclic""#).unwrap();
+        assert!(metric.build().is_err());
+
+        let metric: MetricBuilder = toml::from_str("distance = \"cyclic\"\nperiod = 12.0").unwrap();
+        assert_eq!(metric.build().unwrap(), Metric::Cyclic(12.0));
+
+        let metric: MetricBuilder = toml::from_str(r#"distance = "manhattan""#).unwrap();
+        assert!(metric.check_leveled().is_err());
+
+        let metric: MetricBuilder = toml::from_str(r#"distance = "mahalanobis""#).unwrap();
+        assert!(metric.build().is_err());
+
+        let metric = MetricBuilder::default();
+        assert_eq!(
+            metric.check_leveled().unwrap(),
+            LeveledDistance::Hierarchical
+        );
+
+        let metric: MetricBuilder = toml::from_str(r#"distance = "graph""#).unwrap();
+        assert_eq!(metric.check_leveled().unwrap(), LeveledDistance::Table);
+    }
+}
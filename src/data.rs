@@ -1,3 +1,5 @@
+pub mod category;
+pub mod hierarchy;
 pub mod io;
 pub mod parquet;
 pub mod types;
@@ -0,0 +1,72 @@
+use std::{collections::HashMap, hash::Hash};
+
+/// Dense integer codes `0..len` for category values, shared by all the
+/// columns and files encoded with it.
+pub struct Categories<T> {
+    codes: HashMap<T, usize>,
+    values: Vec<T>,
+}
+
+impl<T: Clone + Eq + Hash> Categories<T> {
+    pub fn new() -> Self {
+        Self {
+            codes: HashMap::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// Code of `value`, assigning the next free code if it is new.
+    pub fn encode(&mut self, value: &T) -> usize {
+        match self.codes.get(value) {
+            Some(code) => *code,
+            None => {
+                let code = self.values.len();
+                self.codes.insert(value.clone(), code);
+                self.values.push(value.clone());
+                code
+            }
+        }
+    }
+
+    pub fn get(&self, value: &T) -> Option<usize> {
+        self.codes.get(value).copied()
+    }
+
+    pub fn value(&self, code: usize) -> &T {
+        &self.values[code]
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+impl<T: Clone + Eq + Hash> Default for Categories<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let mut categories = Categories::new();
+        let my_codes: Vec<usize> = [7, 3, 7, 5, 3]
+            .iter()
+            .map(|x| categories.encode(x))
+            .collect();
+        let ok_codes: Vec<usize> = vec![0, 1, 0, 2, 1];
+        assert_eq!(my_codes, ok_codes);
+        assert_eq!(categories.len(), 3);
+        assert_eq!(categories.get(&5), Some(2));
+        assert_eq!(categories.get(&4), None);
+        assert_eq!(categories.value(1), &3);
+    }
+}
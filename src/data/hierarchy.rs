@@ -0,0 +1,105 @@
+use crate::{data::types::Matrix, error::HierarchyError};
+use std::collections::{HashMap, HashSet};
+
+/// Tree given by the parent of each node. A node is a root when it is its own
+/// parent, or when its parent is not a node of the tree.
+pub struct Hierarchy {
+    parents: HashMap<i32, i32>,
+    nodes: HashSet<i32>,
+}
+
+impl Hierarchy {
+    pub fn new(ids: &[i32], parent_ids: &[i32]) -> Self {
+        let parents: HashMap<i32, i32> = ids
+            .iter()
+            .copied()
+            .zip(parent_ids.iter().copied())
+            .collect();
+        let nodes = ids.iter().chain(parent_ids.iter()).copied().collect();
+        Self { parents, nodes }
+    }
+
+    /// Ancestors of `id` from the root down to `id` itself.
+    pub fn path(&self, id: i32) -> Result<Vec<i32>, HierarchyError> {
+        if !self.nodes.contains(&id) {
+            return Err(HierarchyError::MissingNode(id.to_string()));
+        }
+        let mut path = vec![id];
+        let mut node = id;
+        while let Some(parent) = self.parents.get(&node) {
+            if *parent == node || !self.nodes.contains(parent) {
+                break;
+            }
+            if path.len() > self.parents.len() {
+                return Err(HierarchyError::Cycle(id.to_string()));
+            }
+            path.push(*parent);
+            node = *parent;
+        }
+        path.reverse();
+        Ok(path)
+    }
+
+    pub fn paths(&self, ids: &[i32]) -> Result<Vec<Vec<i32>>, HierarchyError> {
+        ids.iter().map(|id| self.path(*id)).collect()
+    }
+}
+
+/// Paths as rows of a matrix with `depth` levels, aligned at the root. Paths
+/// shorter than `depth` repeat their last node in the deeper levels, so that a
+/// node is at distance zero from itself and matches its descendants at its
+/// own level.
+pub fn pad_paths(paths: &[Vec<i32>], depth: usize) -> Matrix<i32> {
+    let vec = paths
+        .iter()
+        .flat_map(|path| {
+            let last = *path.last().unwrap();
+            path.iter()
+                .copied()
+                .chain(std::iter::repeat(last))
+                .take(depth)
+        })
+        .collect();
+    Matrix::new(vec, depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::distance::hierarchical;
+
+    fn setup() -> Hierarchy {
+        // 1 - 2 - 4 - 6
+        //       - 5
+        //   - 3
+        Hierarchy::new(&[1, 2, 3, 4, 5, 6], &[1, 1, 1, 2, 2, 4])
+    }
+
+    #[test]
+    fn test_path() {
+        let hierarchy = setup();
+        assert_eq!(hierarchy.path(6).unwrap(), vec![1, 2, 4, 6]);
+        assert_eq!(hierarchy.path(3).unwrap(), vec![1, 3]);
+        assert_eq!(hierarchy.path(1).unwrap(), vec![1]);
+        assert!(hierarchy.path(7).is_err());
+
+        let hierarchy = Hierarchy::new(&[1, 2], &[2, 1]);
+        assert!(hierarchy.path(1).is_err());
+    }
+
+    #[test]
+    fn test_pad_paths() {
+        let hierarchy = setup();
+        let paths = hierarchy.paths(&[6, 5, 3, 4]).unwrap();
+        let levels = pad_paths(&paths, 4);
+        assert_eq!(
+            levels.vec,
+            vec![1, 2, 4, 6, 1, 2, 5, 5, 1, 3, 3, 3, 1, 2, 4, 4]
+        );
+
+        let x = levels.rows().nth(3).unwrap();
+        let my_distance: Vec<i32> = levels.rows().map(|y| hierarchical(x, y)).collect();
+        let ok_distance: Vec<i32> = vec![1, 2, 3, 0];
+        assert_eq!(my_distance, ok_distance);
+    }
+}
@@ -1,20 +1,43 @@
 use crate::{data::types::Number, error::Result};
+use parquet::data_type::{ByteArray, ByteArrayType};
 use parquet::file::{properties::WriterProperties, writer::SerializedFileWriter};
 use parquet::schema::parser::parse_message_type;
 use std::{fs::File, sync::Arc};
 
 pub fn write_parquet_col<T: Number>(path: &str, colname: &str, values: &[T]) -> Result<()> {
+    write_parquet_cols(path, colname, values, &[])
+}
+
+/// Write `values` along with the string columns `labels`, given as pairs of
+/// column name and one string per row.
+pub fn write_parquet_cols<T: Number>(
+    path: &str,
+    colname: &str,
+    values: &[T],
+    labels: &[(&str, &[String])],
+) -> Result<()> {
+    for (k, (name, _)) in labels.iter().enumerate() {
+        if *name == colname || labels[..k].iter().any(|(other, _)| other == name) {
+            return Err(format!("column `{}` is written more than once", name).into());
+        }
+    }
     let file = File::create(path)?;
+    let label_types: String = labels
+        .iter()
+        .map(|(name, _)| format!(" REQUIRED BYTE_ARRAY {} (UTF8);", name))
+        .collect();
     let message_type = format!(
-        "message schema {{ REQUIRED {} {}; }}",
+        "message schema {{ REQUIRED {} {};{} }}",
         T::physical_type(),
-        colname
+        colname,
+        label_types
     );
     let schema = Arc::new(parse_message_type(&message_type)?);
     let properties = Arc::new(WriterProperties::builder().build());
     let mut writer = SerializedFileWriter::new(file, schema, properties)?;
 
-    for value_chunk in values.chunks(writer.properties().data_page_row_count_limit()) {
+    let chunk_size = writer.properties().data_page_row_count_limit();
+    for (k, value_chunk) in values.chunks(chunk_size).enumerate() {
         let mut row_group_writer = writer.next_row_group()?;
         let mut col_writer = row_group_writer
             .next_column()?
@@ -23,8 +46,98 @@ pub fn write_parquet_col<T: Number>(path: &str, colname: &str, values: &[T]) ->
             .typed::<T::D>()
             .write_batch(value_chunk, None, None)?;
         col_writer.close()?;
+        for (_, label) in labels {
+            let label_chunk: Vec<ByteArray> = label[k * chunk_size..]
+                .iter()
+                .take(value_chunk.len())
+                .map(|s| ByteArray::from(s.as_str()))
+                .collect();
+            let mut col_writer = row_group_writer
+                .next_column()?
+                .ok_or("trouble with colunm writer")?;
+            col_writer
+                .typed::<ByteArrayType>()
+                .write_batch(&label_chunk, None, None)?;
+            col_writer.close()?;
+        }
         row_group_writer.close()?;
     }
     writer.close()?;
     Ok(())
 }
+
+/// Path of a temporary file named `name`, unique to this test process.
+#[cfg(test)]
+pub fn temp_path(name: &str) -> String {
+    let file = format!("weavers_test_{}_{}.parquet", name, std::process::id());
+    std::env::temp_dir()
+        .join(file)
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Column of a test Parquet file, nulls are `None`.
+#[cfg(test)]
+pub enum TestCol {
+    Int(Vec<Option<i32>>),
+    Long(Vec<Option<i64>>),
+    Float(Vec<Option<f32>>),
+    Double(Vec<Option<f64>>),
+    Str(Vec<Option<&'static str>>),
+}
+
+/// Write a test Parquet file with one `OPTIONAL` column per pair of name and
+/// values.
+#[cfg(test)]
+pub fn write_test_cols(path: &str, cols: &[(&str, TestCol)]) {
+    use parquet::{
+        data_type::{DataType, DoubleType, FloatType, Int32Type, Int64Type},
+        file::writer::SerializedColumnWriter,
+    };
+
+    fn write_batch<D: DataType>(writer: &mut SerializedColumnWriter<'_>, values: &[Option<D::T>])
+    where
+        D::T: Clone,
+    {
+        let def_levels: Vec<i16> = values.iter().map(|v| v.is_some() as i16).collect();
+        let values: Vec<D::T> = values.iter().flatten().cloned().collect();
+        writer
+            .typed::<D>()
+            .write_batch(&values, Some(&def_levels), None)
+            .unwrap();
+    }
+
+    let fields: String = cols
+        .iter()
+        .map(|(name, col)| match col {
+            TestCol::Int(_) => format!(" OPTIONAL INT32 {};", name),
+            TestCol::Long(_) => format!(" OPTIONAL INT64 {};", name),
+            TestCol::Float(_) => format!(" OPTIONAL FLOAT {};", name),
+            TestCol::Double(_) => format!(" OPTIONAL DOUBLE {};", name),
+            TestCol::Str(_) => format!(" OPTIONAL BYTE_ARRAY {} (UTF8);", name),
+        })
+        .collect();
+    let schema = Arc::new(parse_message_type(&format!("message schema {{{} }}", fields)).unwrap());
+    let properties = Arc::new(WriterProperties::builder().build());
+    let file = File::create(path).unwrap();
+    let mut writer = SerializedFileWriter::new(file, schema, properties).unwrap();
+    let mut row_group_writer = writer.next_row_group().unwrap();
+    for (_, col) in cols {
+        let mut col_writer = row_group_writer.next_column().unwrap().unwrap();
+        match col {
+            TestCol::Int(values) => write_batch::<Int32Type>(&mut col_writer, values),
+            TestCol::Long(values) => write_batch::<Int64Type>(&mut col_writer, values),
+            TestCol::Float(values) => write_batch::<FloatType>(&mut col_writer, values),
+            TestCol::Double(values) => write_batch::<DoubleType>(&mut col_writer, values),
+            TestCol::Str(values) => {
+                let values: Vec<Option<ByteArray>> =
+                    values.iter().map(|v| v.map(ByteArray::from)).collect();
+                write_batch::<ByteArrayType>(&mut col_writer, &values)
+            }
+        }
+        col_writer.close().unwrap();
+    }
+    row_group_writer.close().unwrap();
+    writer.close().unwrap();
+}
@@ -3,7 +3,9 @@ use crate::{
     error::{ColumnError, Result},
 };
 use parquet::{
+    basic::Type as PhysicalType,
     file::reader::{self, FileReader},
+    record::Field,
     schema::types::Type,
 };
 use std::{collections::HashMap, fs, sync::Arc};
@@ -49,6 +51,66 @@ impl ParquetFileReader {
     }
 
     pub fn read_cols<T: Number>(&self, cols: &[String]) -> Result<Matrix<T>> {
+        self.read_typed_cols(cols, |field| {
+            T::from_field(field).expect("err converting field")
+        })
+    }
+
+    /// Physical types of the columns.
+    pub fn col_types(&self, cols: &[String]) -> Result<Vec<PhysicalType>> {
+        let projection = self.build_projection(cols)?;
+        Ok(projection
+            .get_fields()
+            .iter()
+            .map(|field| field.get_physical_type())
+            .collect())
+    }
+
+    /// Read `INT32`, `INT64` or `BYTE_ARRAY` columns as category keys, nulls
+    /// are `None`.
+    pub fn read_key_cols(&self, cols: &[String]) -> Result<Matrix<Option<String>>> {
+        let projection = self.build_projection(cols)?;
+        for field in projection.get_fields() {
+            match field.get_physical_type() {
+                PhysicalType::INT32 | PhysicalType::INT64 | PhysicalType::BYTE_ARRAY => {}
+                _ => return Err(Box::new(ColumnError::TypeMismatch(field.name().to_owned()))),
+            }
+        }
+        let vec = self
+            .0
+            .get_row_iter(Some(projection))?
+            .flat_map(|row| {
+                row.expect("err getting row")
+                    .get_column_iter()
+                    .map(|(_, field)| match field {
+                        Field::Null => None,
+                        Field::Int(v) => Some(v.to_string()),
+                        Field::Long(v) => Some(v.to_string()),
+                        Field::Str(v) => Some(v.clone()),
+                        Field::Bytes(v) => Some(String::from_utf8_lossy(v.data()).into_owned()),
+                        _ => panic!("err converting field"),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Ok(Matrix::new(vec, cols.len()))
+    }
+
+    /// Read columns like [`Self::read_cols`], replacing nulls with `missing`.
+    pub fn read_cols_or<T: Number>(&self, cols: &[String], missing: T) -> Result<Matrix<T>> {
+        self.read_typed_cols(cols, |field| match field {
+            Field::Null => missing,
+            _ => T::from_field(field).expect("err converting field"),
+        })
+    }
+
+    /// Read columns of the physical type of `T`, converting each field with
+    /// `convert`.
+    fn read_typed_cols<T: Number>(
+        &self,
+        cols: &[String],
+        convert: impl Fn(&Field) -> T,
+    ) -> Result<Matrix<T>> {
         let projection = self.build_projection(cols)?;
         for field in projection.get_fields() {
             if field.get_physical_type() != T::physical_type() {
@@ -61,7 +123,59 @@ impl ParquetFileReader {
             .flat_map(|row| {
                 row.expect("err getting row")
                     .get_column_iter()
-                    .map(|(_, field)| T::from_field(field).expect("err converting field"))
+                    .map(|(_, field)| convert(field))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Ok(Matrix::new(vec, cols.len()))
+    }
+
+    /// Read `INT32` or `INT64` columns, converting all values to `i64`.
+    pub fn read_int_cols(&self, cols: &[String]) -> Result<Matrix<i64>> {
+        let projection = self.build_projection(cols)?;
+        for field in projection.get_fields() {
+            match field.get_physical_type() {
+                PhysicalType::INT32 | PhysicalType::INT64 => {}
+                _ => return Err(Box::new(ColumnError::TypeMismatch(field.name().to_owned()))),
+            }
+        }
+        let vec = self
+            .0
+            .get_row_iter(Some(projection))?
+            .flat_map(|row| {
+                row.expect("err getting row")
+                    .get_column_iter()
+                    .map(|(_, field)| match field {
+                        Field::Int(v) => *v as i64,
+                        Field::Long(v) => *v,
+                        _ => panic!("err converting field"),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        Ok(Matrix::new(vec, cols.len()))
+    }
+
+    /// Read `FLOAT` or `DOUBLE` columns, converting all values to `f32`.
+    pub fn read_float_cols(&self, cols: &[String]) -> Result<Matrix<f32>> {
+        let projection = self.build_projection(cols)?;
+        for field in projection.get_fields() {
+            match field.get_physical_type() {
+                PhysicalType::FLOAT | PhysicalType::DOUBLE => {}
+                _ => return Err(Box::new(ColumnError::TypeMismatch(field.name().to_owned()))),
+            }
+        }
+        let vec = self
+            .0
+            .get_row_iter(Some(projection))?
+            .flat_map(|row| {
+                row.expect("err getting row")
+                    .get_column_iter()
+                    .map(|(_, field)| match field {
+                        Field::Float(v) => *v,
+                        Field::Double(v) => *v as f32,
+                        _ => panic!("err converting field"),
+                    })
                     .collect::<Vec<_>>()
             })
             .collect();
@@ -3,6 +3,7 @@ use parquet::data_type::{BoolType, DataType, DoubleType, FloatType, Int32Type, I
 use parquet::record::Field;
 use std::{
     fmt::{Debug, Display},
+    ops::Range,
     slice::Chunks,
     sync::atomic::{AtomicU32, Ordering},
 };
@@ -89,13 +90,41 @@ impl<T> Matrix<T> {
         Self { vec, ncols }
     }
 
-    pub fn rows(&self) -> Chunks<T> {
+    pub fn nrows(&self) -> usize {
+        self.vec.len() / self.ncols
+    }
+
+    pub fn rows(&self) -> Chunks<'_, T> {
         self.vec.chunks(self.ncols)
     }
 
+    /// Rows in the range, as [`Self::rows`].
+    pub fn rows_in(&self, range: Range<usize>) -> Chunks<'_, T> {
+        self.vec[range.start * self.ncols..range.end * self.ncols].chunks(self.ncols)
+    }
+
     pub fn to_vec(self) -> Vec<T> {
         self.vec
     }
+
+    pub fn select_rows(&self, rows: &[usize]) -> Self
+    where
+        T: Clone,
+    {
+        let vec = rows
+            .iter()
+            .flat_map(|i| {
+                self.vec[i * self.ncols..(i + 1) * self.ncols]
+                    .iter()
+                    .cloned()
+            })
+            .collect();
+        Self::new(vec, self.ncols)
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> Matrix<U> {
+        Matrix::new(self.vec.into_iter().map(f).collect(), self.ncols)
+    }
 }
 
 pub struct AtomicF32(AtomicU32);
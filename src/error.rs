@@ -10,3 +10,25 @@ pub enum ColumnError {
     #[error("column `{0}`'s type does not match")]
     TypeMismatch(String),
 }
+
+#[derive(thiserror::Error, Debug)]
+pub enum ConfigError {
+    #[error("distance `{0}` requires parameter `{1}`")]
+    MissingParameter(String, String),
+    #[error("parameter `{0}` is invalid: {1}")]
+    InvalidParameter(String, String),
+    #[error("distance `{0}` is not supported by {1} kernels")]
+    UnsupportedDistance(String, String),
+    #[error("distance between `{0}` and `{1}` is missing from `{2}`")]
+    MissingDistance(String, String, String),
+    #[error("parameter `{0}` is missing for stratum {2} of `{1}`")]
+    MissingStratum(String, String, String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum HierarchyError {
+    #[error("`{0}` is missing from the hierarchy")]
+    MissingNode(String),
+    #[error("hierarchy has a cycle through `{0}`")]
+    Cycle(String),
+}
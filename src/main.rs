@@ -1,6 +1,33 @@
+use log::{LevelFilter, Log, Metadata, Record};
 use weavers::app::Application;
 
+/// Print the warnings and notes of the library to stderr.
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{}: {}",
+                record.level().as_str().to_lowercase(),
+                record.args()
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
 fn main() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
     // TODO: hanle command line argument more elegantly
     let args: Vec<String> = std::env::args().collect();
     let app = Application::new().load_model(&args[1]).unwrap();
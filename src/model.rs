@@ -3,12 +3,19 @@ pub mod distance;
 pub mod kernel;
 
 use crate::{config::Output, model::dimenion::Dimension};
+use std::ops::Range;
 
 pub struct Weave {
     pub dimensions: Vec<Dimension>,
     pub lens: (usize, usize),
     pub values: Vec<f32>,
     pub output: Output,
+    /// Data rows in the group of each prediction row, when the data rows are
+    /// partitioned into groups.
+    pub groups: Option<Vec<Range<usize>>>,
+    /// Label of the kernel parameters used for each prediction row, written
+    /// as a diagnostic output column.
+    pub params: Option<Vec<String>>,
 }
 
 impl Weave {
@@ -23,16 +30,44 @@ impl Weave {
             values,
             lens,
             output,
+            groups: None,
+            params: None,
         }
     }
 
+    /// Partition the data rows into groups, each prediction row only uses
+    /// the data rows of its group. `groups` are the group codes of the data
+    /// and prediction rows.
+    pub fn with_groups(mut self, groups: (&[usize], &[usize])) -> Self {
+        let (order, ranges) = partition(groups.0, groups.1);
+        self.dimensions
+            .iter_mut()
+            .for_each(|dim| dim.select_data(&order));
+        self.values = order.iter().map(|i| self.values[*i]).collect();
+        self.groups = Some(ranges);
+        self
+    }
+
+    /// Weighted average of the values for prediction row `i`. It is NaN when
+    /// the group of the row has no data rows, or when all the weights are
+    /// zero, e.g. no data row is within the support of a compact kernel.
     pub fn avg_for(&self, i: usize) -> f32 {
-        let mut weight: Vec<f32> = vec![1.0; self.lens.0];
+        let rows = match &self.groups {
+            Some(groups) => groups[i].clone(),
+            None => 0..self.lens.0,
+        };
+        if rows.is_empty() {
+            return f32::NAN;
+        }
+        let mut weight: Vec<f32> = vec![1.0; rows.len()];
         for dim in &self.dimensions {
-            dim.update_weight(i, &mut weight);
+            dim.update_weight(i, rows.clone(), &mut weight);
         }
         let s: f32 = weight.iter().sum();
-        self.values
+        if s == 0.0 {
+            return f32::NAN;
+        }
+        self.values[rows]
             .iter()
             .zip(weight.iter())
             .map(|(x, w)| x * w / s)
@@ -40,11 +75,33 @@ impl Weave {
     }
 }
 
+/// Order of the data rows sorting them by group, and the range of the ordered
+/// data rows in the group of each prediction row.
+pub fn partition(data_groups: &[usize], pred_groups: &[usize]) -> (Vec<usize>, Vec<Range<usize>>) {
+    let mut order: Vec<usize> = (0..data_groups.len()).collect();
+    order.sort_by_key(|i| data_groups[*i]);
+
+    let ngroups = data_groups
+        .iter()
+        .chain(pred_groups.iter())
+        .max()
+        .map_or(0, |g| g + 1);
+    let mut bounds = vec![0; ngroups + 1];
+    data_groups.iter().for_each(|g| bounds[g + 1] += 1);
+    (0..ngroups).for_each(|g| bounds[g + 1] += bounds[g]);
+
+    let ranges = pred_groups
+        .iter()
+        .map(|g| bounds[*g]..bounds[g + 1])
+        .collect();
+    (order, ranges)
+}
+
 #[cfg(test)]
 mod tests {
     use super::{
         dimenion::{Dimension, DimensionHandle},
-        kernel::{Exponential, Tricubic},
+        kernel::{Compact, CompactShape, Exponential, Gaussian, Tricubic},
         *,
     };
     use crate::data::types::Matrix;
@@ -64,17 +121,27 @@ mod tests {
             Matrix::new(vec![0_f32], 1),
         ));
 
+        // dimension 2
+        let dim2 = Dimension::GenericGaussian(DimensionHandle::new(
+            Gaussian::new(1.0),
+            Matrix::new(vec![0_f32, 1_f32], 1),
+            Matrix::new(vec![0_f32], 1),
+        ));
+
         let values = vec![1_f32, 1_f32];
         let output = Output {
             path: "example/result.parquet".to_string(),
             values: "prediction".to_string(),
+            params: None,
         };
 
         Weave {
-            dimensions: vec![dim0, dim1],
+            dimensions: vec![dim0, dim1, dim2],
             values,
             lens: (2, 1),
             output,
+            groups: None,
+            params: None,
         }
     }
 
@@ -85,4 +152,52 @@ mod tests {
         let tr_avg = 1_f32;
         assert_eq!(my_avg, tr_avg);
     }
+
+    #[test]
+    fn test_partition() {
+        let (order, ranges) = partition(&[1, 0, 1, 2], &[1, 3, 0]);
+        assert_eq!(order, vec![1, 0, 2, 3]);
+        assert_eq!(ranges, vec![1..3, 4..4, 0..1]);
+    }
+
+    #[test]
+    fn test_groups() {
+        let dim = Dimension::GenericExponential(DimensionHandle::new(
+            Exponential::new(1.0),
+            Matrix::new(vec![0_f32, 1_f32, 2_f32], 1),
+            Matrix::new(vec![0_f32, 0_f32, 0_f32], 1),
+        ));
+        let output = Output {
+            path: "example/result.parquet".to_string(),
+            values: "prediction".to_string(),
+            params: None,
+        };
+        let model = Weave::new(vec![dim], vec![1.0, 2.0, 3.0], (3, 3), output)
+            .with_groups((&[0, 1, 0], &[1, 0, 2]));
+
+        assert_eq!(model.avg_for(0), 2.0);
+        let w = (-2.0_f32).exp();
+        assert_eq!(model.avg_for(1), (1.0 + 3.0 * w) / (1.0 + w));
+        // no data rows in the group of the prediction row
+        assert!(model.avg_for(2).is_nan());
+    }
+
+    #[test]
+    fn test_outside_support() {
+        let dim = Dimension::GenericEpanechnikov(DimensionHandle::new(
+            Compact::new(1.0, CompactShape::Epanechnikov),
+            Matrix::new(vec![0_f32, 1_f32], 1),
+            Matrix::new(vec![0.5_f32, 3_f32], 1),
+        ));
+        let output = Output {
+            path: "example/result.parquet".to_string(),
+            values: "prediction".to_string(),
+            params: None,
+        };
+        let model = Weave::new(vec![dim], vec![1.0, 3.0], (2, 2), output);
+
+        assert_eq!(model.avg_for(0), 2.0);
+        // no data row is within the radius of the prediction row
+        assert!(model.avg_for(1).is_nan());
+    }
 }
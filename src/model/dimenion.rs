@@ -1,20 +1,30 @@
-use super::kernel::{Exponential, Kernel, Leveled, Tricubic};
-use crate::data::types::Matrix;
+use super::kernel::{
+    AdaptiveTricubic, Asymmetric, BoxedKernel, Compact, Exponential, Gaussian, Indicator,
+    InverseDistance, Kernel, Leveled, Matern, Rescale, Tricubic,
+};
+use crate::{
+    data::types::Matrix,
+    error::{ConfigError, Result},
+};
+use std::ops::Range;
 
 pub trait GenericWorker {
-    fn update_weight(&self, i: usize, weight: &mut [f32]);
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]);
 }
 
 pub trait CategoricalWorker {
-    fn update_weight(&self, i: usize, weight: &mut [f32]);
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]);
 }
 
 pub trait AdaptiveWorker {
-    fn update_weight(&self, i: usize, weight: &mut [f32]);
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]);
 }
 
 pub struct DimensionHandle<K: Kernel> {
     kernel: K,
+    /// Kernel of each prediction row, when the kernel parameters vary by
+    /// prediction row.
+    pred_kernels: Option<Vec<K>>,
     coord_data: Matrix<K::CType>,
     coord_pred: Matrix<K::CType>,
 }
@@ -22,30 +32,80 @@ impl<K: Kernel> DimensionHandle<K> {
     pub fn new(kernel: K, coord_data: Matrix<K::CType>, coord_pred: Matrix<K::CType>) -> Self {
         Self {
             kernel,
+            pred_kernels: None,
             coord_data,
             coord_pred,
         }
     }
+
+    /// Use a separate kernel for each prediction row.
+    pub fn with_pred_kernels(self, pred_kernels: Vec<K>) -> Result<Self> {
+        if pred_kernels.len() != self.coord_pred.nrows() {
+            return Err(Box::new(ConfigError::InvalidParameter(
+                "kernels".to_string(),
+                format!(
+                    "expected one kernel per prediction row, got {} for {} rows",
+                    pred_kernels.len(),
+                    self.coord_pred.nrows()
+                ),
+            )));
+        }
+        Ok(Self {
+            pred_kernels: Some(pred_kernels),
+            ..self
+        })
+    }
+
+    /// Kernel of prediction row `i`.
+    pub fn kernel_for(&self, i: usize) -> &K {
+        match &self.pred_kernels {
+            Some(kernels) => &kernels[i],
+            None => &self.kernel,
+        }
+    }
+
+    /// Keep only the given data rows, in the given order.
+    pub fn select_data(&mut self, rows: &[usize])
+    where
+        K::CType: Clone,
+    {
+        self.coord_data = self.coord_data.select_rows(rows);
+    }
+}
+impl<K: Rescale> DimensionHandle<K> {
+    /// Use the kernel with the given radius for each prediction row, or the
+    /// single kernel for `None`.
+    pub fn with_pred_radius(self, radius: Option<Vec<f32>>) -> Result<Self> {
+        match radius {
+            Some(radius) => {
+                let kernels = radius.iter().map(|r| self.kernel.with_radius(*r)).collect();
+                self.with_pred_kernels(kernels)
+            }
+            None => Ok(self),
+        }
+    }
 }
 
 impl<K: Kernel> GenericWorker for DimensionHandle<K> {
-    fn update_weight(&self, i: usize, weight: &mut [f32]) {
-        let x = self.coord_data.rows().nth(i).unwrap();
-        self.coord_pred
-            .rows()
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]) {
+        let x = self.coord_pred.rows().nth(i).unwrap();
+        let kernel = self.kernel_for(i);
+        self.coord_data
+            .rows_in(rows)
             .zip(weight.iter_mut())
-            .for_each(|(y, w)| *w *= self.kernel.kernel(x, y))
+            .filter(|(_, w)| **w != 0.0)
+            .for_each(|(y, w)| *w *= kernel.kernel(x, y))
     }
 }
 
 impl CategoricalWorker for DimensionHandle<Leveled> {
-    fn update_weight(&self, i: usize, weight: &mut [f32]) {
-        let x = self.coord_data.rows().nth(i).unwrap();
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]) {
+        let x = self.coord_pred.rows().nth(i).unwrap();
         let mut weight_sum: Vec<f32> = vec![0.0; self.kernel.maxlvl as usize + 1];
 
         let distance: Vec<i32> = self
-            .coord_pred
-            .rows()
+            .coord_data
+            .rows_in(rows)
             .zip(weight.iter())
             .map(|(y, w)| {
                 let d = self.kernel.distance(x, y);
@@ -66,23 +126,17 @@ impl CategoricalWorker for DimensionHandle<Leveled> {
     }
 }
 
-impl AdaptiveWorker for DimensionHandle<Tricubic> {
-    fn update_weight(&self, i: usize, weight: &mut [f32]) {
-        let x = self.coord_data.rows().nth(i).unwrap();
+impl AdaptiveWorker for DimensionHandle<AdaptiveTricubic> {
+    fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]) {
+        let x = self.coord_pred.rows().nth(i).unwrap();
         let distance: Vec<f32> = self
-            .coord_pred
-            .rows()
+            .coord_data
+            .rows_in(rows)
             .map(|y| self.kernel.distance(x, y))
             .collect();
-        let radius = distance
-            .iter()
-            .max_by(|x, y| x.partial_cmp(y).unwrap())
-            .map(|x| x + 1.0)
-            .unwrap();
-        let kernel = Tricubic {
-            radius,
-            ..self.kernel
-        };
+        let kernel = self
+            .kernel
+            .with_radius(self.kernel.bandwidth.radius(&distance));
         distance
             .iter()
             .zip(weight.iter_mut())
@@ -93,19 +147,74 @@ impl AdaptiveWorker for DimensionHandle<Tricubic> {
 pub enum Dimension {
     GenericExponential(DimensionHandle<Exponential>),
     GenericTricubic(DimensionHandle<Tricubic>),
+    GenericGaussian(DimensionHandle<Gaussian>),
+    GenericEpanechnikov(DimensionHandle<Compact>),
+    GenericBiweight(DimensionHandle<Compact>),
+    GenericTriweight(DimensionHandle<Compact>),
+    GenericUniform(DimensionHandle<Compact>),
+    GenericMatern(DimensionHandle<Matern>),
+    GenericInverseDistance(DimensionHandle<InverseDistance>),
     GenericLeveled(DimensionHandle<Leveled>),
     CategoricalLeveled(DimensionHandle<Leveled>),
-    AdaptiveTricubic(DimensionHandle<Tricubic>),
+    AdaptiveTricubic(DimensionHandle<AdaptiveTricubic>),
+    GenericIndicator(DimensionHandle<Indicator>),
+    Covariate(DimensionHandle<BoxedKernel>),
+    GenericComposite(DimensionHandle<BoxedKernel>),
+    GenericAsymmetric(DimensionHandle<Asymmetric>),
 }
 
 impl Dimension {
-    pub fn update_weight(&self, i: usize, weight: &mut [f32]) {
+    pub fn update_weight(&self, i: usize, rows: Range<usize>, weight: &mut [f32]) {
         match self {
-            Self::GenericExponential(handle) => GenericWorker::update_weight(handle, i, weight),
-            Self::GenericTricubic(handle) => GenericWorker::update_weight(handle, i, weight),
-            Self::GenericLeveled(handle) => GenericWorker::update_weight(handle, i, weight),
-            Self::CategoricalLeveled(handle) => CategoricalWorker::update_weight(handle, i, weight),
-            Self::AdaptiveTricubic(handle) => AdaptiveWorker::update_weight(handle, i, weight),
+            Self::GenericExponential(handle) => {
+                GenericWorker::update_weight(handle, i, rows, weight)
+            }
+            Self::GenericTricubic(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericGaussian(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericEpanechnikov(handle) => {
+                GenericWorker::update_weight(handle, i, rows, weight)
+            }
+            Self::GenericBiweight(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericTriweight(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericUniform(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericMatern(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericInverseDistance(handle) => {
+                GenericWorker::update_weight(handle, i, rows, weight)
+            }
+            Self::GenericLeveled(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::CategoricalLeveled(handle) => {
+                CategoricalWorker::update_weight(handle, i, rows, weight)
+            }
+            Self::AdaptiveTricubic(handle) => {
+                AdaptiveWorker::update_weight(handle, i, rows, weight)
+            }
+            Self::GenericIndicator(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::Covariate(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericComposite(handle) => GenericWorker::update_weight(handle, i, rows, weight),
+            Self::GenericAsymmetric(handle) => {
+                GenericWorker::update_weight(handle, i, rows, weight)
+            }
+        }
+    }
+
+    pub fn select_data(&mut self, rows: &[usize]) {
+        match self {
+            Self::GenericExponential(handle) => handle.select_data(rows),
+            Self::GenericTricubic(handle) => handle.select_data(rows),
+            Self::GenericGaussian(handle) => handle.select_data(rows),
+            Self::GenericEpanechnikov(handle) => handle.select_data(rows),
+            Self::GenericBiweight(handle) => handle.select_data(rows),
+            Self::GenericTriweight(handle) => handle.select_data(rows),
+            Self::GenericUniform(handle) => handle.select_data(rows),
+            Self::GenericMatern(handle) => handle.select_data(rows),
+            Self::GenericInverseDistance(handle) => handle.select_data(rows),
+            Self::GenericLeveled(handle) => handle.select_data(rows),
+            Self::CategoricalLeveled(handle) => handle.select_data(rows),
+            Self::AdaptiveTricubic(handle) => handle.select_data(rows),
+            Self::GenericIndicator(handle) => handle.select_data(rows),
+            Self::Covariate(handle) => handle.select_data(rows),
+            Self::GenericComposite(handle) => handle.select_data(rows),
+            Self::GenericAsymmetric(handle) => handle.select_data(rows),
         }
     }
 }
@@ -113,30 +222,112 @@ impl Dimension {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::kernel::{Bandwidth, CompactShape};
 
     #[test]
     fn test_generic_update_weight() {
         let handle = DimensionHandle::new(
             Leveled::new(0.5, 3),
-            Matrix::new(vec![0, 1, 2], 3),
             Matrix::new(vec![0, 1, 2, 0, 1, 8, 0, 6, 7, 3, 4, 5], 3),
+            Matrix::new(vec![0, 1, 2], 3),
         );
         let mut my_weight: Vec<f32> = vec![1.0; 4];
-        GenericWorker::update_weight(&handle, 0, &mut my_weight);
+        GenericWorker::update_weight(&handle, 0, 0..4, &mut my_weight);
         let ok_weight = vec![0.5, 0.25, 0.25, 0.0];
         assert_eq!(my_weight, ok_weight);
     }
 
+    #[test]
+    fn test_update_weight_orientation() {
+        // one weight per data row, for the prediction row `i`
+        let handle = DimensionHandle::new(
+            Leveled::new(0.5, 1),
+            Matrix::new(vec![1, 2, 1], 1),
+            Matrix::new(vec![2, 1], 1),
+        );
+        let mut my_weight: Vec<f32> = vec![1.0; 3];
+        GenericWorker::update_weight(&handle, 0, 0..3, &mut my_weight);
+        assert_eq!(my_weight, vec![0.0, 1.0, 0.0]);
+
+        let mut my_weight: Vec<f32> = vec![1.0; 3];
+        CategoricalWorker::update_weight(&handle, 1, 0..3, &mut my_weight);
+        assert_eq!(my_weight, vec![0.5, 0.0, 0.5]);
+    }
+
+    #[test]
+    fn test_pred_radius_update_weight() {
+        let handle = DimensionHandle::new(
+            Compact::new(1.0, CompactShape::Uniform),
+            Matrix::new(vec![0.0, 1.0, 2.0, 4.0], 1),
+            Matrix::new(vec![0.0, 0.0], 1),
+        )
+        .with_pred_radius(Some(vec![1.5, 3.0]))
+        .unwrap();
+
+        let mut my_weight: Vec<f32> = vec![1.0; 4];
+        GenericWorker::update_weight(&handle, 0, 0..4, &mut my_weight);
+        assert_eq!(my_weight, vec![1.0, 1.0, 0.0, 0.0]);
+
+        let mut my_weight: Vec<f32> = vec![1.0; 4];
+        GenericWorker::update_weight(&handle, 1, 0..4, &mut my_weight);
+        assert_eq!(my_weight, vec![1.0, 1.0, 1.0, 0.0]);
+
+        // one kernel per prediction row
+        let handle = DimensionHandle::new(
+            Compact::new(1.0, CompactShape::Uniform),
+            Matrix::new(vec![0.0, 1.0], 1),
+            Matrix::new(vec![0.0, 0.0], 1),
+        );
+        assert!(handle.with_pred_radius(Some(vec![1.5])).is_err());
+    }
+
     #[test]
     fn test_categorical_update_weight() {
         let handle = DimensionHandle::new(
             Leveled::new(0.5, 3),
-            Matrix::new(vec![0, 1, 2], 3),
             Matrix::new(vec![0, 1, 2, 0, 1, 8, 0, 6, 7, 3, 4, 5], 3),
+            Matrix::new(vec![0, 1, 2], 3),
         );
         let mut my_weight: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0];
-        CategoricalWorker::update_weight(&handle, 0, &mut my_weight);
+        CategoricalWorker::update_weight(&handle, 0, 0..4, &mut my_weight);
         let ok_weight = vec![0.5, 0.25, 0.25, 0.0];
         assert_eq!(my_weight, ok_weight);
     }
+
+    #[test]
+    fn test_adaptive_update_weight() {
+        let handle = DimensionHandle::new(
+            AdaptiveTricubic::new(Tricubic::new(1.0, 1.0), Bandwidth::Nearest(2)),
+            Matrix::new(vec![0.0, 1.0, 2.0, 4.0], 1),
+            Matrix::new(vec![0.0], 1),
+        );
+        let mut my_weight: Vec<f32> = vec![1.0; 4];
+        AdaptiveWorker::update_weight(&handle, 0, 0..4, &mut my_weight);
+        assert_eq!(my_weight[0], 1.0);
+        assert!(my_weight[1] > 0.0 && my_weight[1] < 1e-6);
+        assert_eq!(my_weight[2..], [0.0, 0.0]);
+
+        let handle = DimensionHandle::new(
+            AdaptiveTricubic::new(Tricubic::new(1.0, 1.0), Bandwidth::Quantile(0.75)),
+            Matrix::new(vec![0.0, 1.0, 2.0, 4.0], 1),
+            Matrix::new(vec![0.0], 1),
+        );
+        let mut my_weight: Vec<f32> = vec![1.0; 4];
+        AdaptiveWorker::update_weight(&handle, 0, 0..4, &mut my_weight);
+        assert_eq!(my_weight[0], 1.0);
+        assert!((my_weight[1] - 0.125).abs() < 1e-6);
+        assert!(my_weight[2] > 0.0 && my_weight[2] < 1e-6);
+        assert_eq!(my_weight[3], 0.0);
+
+        // the nearest data point keeps a positive weight with `k = 1`
+        let handle = DimensionHandle::new(
+            AdaptiveTricubic::new(Tricubic::new(1.0, 3.0), Bandwidth::Nearest(1)),
+            Matrix::new(vec![1.0, 3.0], 1),
+            Matrix::new(vec![0.5], 1),
+        );
+        let mut my_weight: Vec<f32> = vec![1.0; 2];
+        AdaptiveWorker::update_weight(&handle, 0, 0..2, &mut my_weight);
+        assert!(my_weight[0] > 0.0);
+        assert_eq!(my_weight[1], 0.0);
+    }
 }
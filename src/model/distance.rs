@@ -1,17 +1,313 @@
+use std::{cmp::Ordering, collections::BinaryHeap, sync::Arc};
+
 #[inline]
 pub fn euclidean(x: &[f32], y: &[f32]) -> f32 {
-    (x[0] - y[0]).abs()
+    x.iter()
+        .zip(y.iter())
+        .map(|(xi, yi)| (xi - yi) * (xi - yi))
+        .sum::<f32>()
+        .sqrt()
+}
+
+#[inline]
+pub fn manhattan(x: &[f32], y: &[f32]) -> f32 {
+    x.iter().zip(y.iter()).map(|(xi, yi)| (xi - yi).abs()).sum()
+}
+
+#[inline]
+pub fn chebyshev(x: &[f32], y: &[f32]) -> f32 {
+    x.iter()
+        .zip(y.iter())
+        .map(|(xi, yi)| (xi - yi).abs())
+        .fold(0.0, f32::max)
+}
+
+#[inline]
+pub fn minkowski(x: &[f32], y: &[f32], p: f32) -> f32 {
+    x.iter()
+        .zip(y.iter())
+        .map(|(xi, yi)| (xi - yi).abs().powf(p))
+        .sum::<f32>()
+        .powf(1.0 / p)
+}
+
+/// Euclidean distance with every column wrapped around `period`, so that the
+/// difference in each column is `min(|x - y|, period - |x - y|)`.
+#[inline]
+pub fn cyclic(x: &[f32], y: &[f32], period: f32) -> f32 {
+    x.iter()
+        .zip(y.iter())
+        .map(|(xi, yi)| {
+            let d = (xi - yi).abs() % period;
+            let d = d.min(period - d);
+            d * d
+        })
+        .sum::<f32>()
+        .sqrt()
+}
+
+/// Mahalanobis distance `sqrt((x - y)' A (x - y))` with `precision` `A`, the
+/// inverse of the covariance matrix, stored row by row.
+#[inline]
+pub fn mahalanobis(x: &[f32], y: &[f32], precision: &[f32]) -> f32 {
+    precision
+        .chunks(x.len())
+        .zip(x.iter().zip(y.iter()))
+        .map(|(row, (xi, yi))| {
+            (xi - yi)
+                * row
+                    .iter()
+                    .zip(x.iter().zip(y.iter()))
+                    .map(|(a, (xj, yj))| a * (xj - yj))
+                    .sum::<f32>()
+        })
+        .sum::<f32>()
+        .max(0.0)
+        .sqrt()
+}
+
+/// Inverse of the `n` by `n` matrix stored row by row, `None` when it is
+/// singular.
+pub fn invert(matrix: &[f32], n: usize) -> Option<Vec<f32>> {
+    let mut a: Vec<f64> = matrix.iter().map(|x| *x as f64).collect();
+    let mut inv: Vec<f64> = (0..n * n)
+        .map(|k| if k / n == k % n { 1.0 } else { 0.0 })
+        .collect();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|i, j| a[i * n + col].abs().total_cmp(&a[j * n + col].abs()))?;
+        if a[pivot * n + col].abs() < 1e-12 {
+            return None;
+        }
+        for k in 0..n {
+            a.swap(col * n + k, pivot * n + k);
+            inv.swap(col * n + k, pivot * n + k);
+        }
+        let p = a[col * n + col];
+        for k in 0..n {
+            a[col * n + k] /= p;
+            inv[col * n + k] /= p;
+        }
+        for row in (0..n).filter(|row| *row != col) {
+            let f = a[row * n + col];
+            for k in 0..n {
+                a[row * n + k] -= f * a[col * n + k];
+                inv[row * n + k] -= f * inv[col * n + k];
+            }
+        }
+    }
+    Some(inv.iter().map(|x| *x as f32).collect())
 }
 
+/// Mean radius of the earth in kilometres.
+pub const EARTH_RADIUS: f32 = 6371.0;
+
+/// Great-circle distance in kilometres between `[lat, lon]` points given in
+/// degrees.
+#[inline]
+pub fn haversine(x: &[f32], y: &[f32]) -> f32 {
+    let (lat0, lat1) = (x[0].to_radians(), y[0].to_radians());
+    let half_dlat = 0.5 * (lat1 - lat0);
+    let half_dlon = 0.5 * (y[1] - x[1]).to_radians();
+    let a = half_dlat.sin().powi(2) + lat0.cos() * lat1.cos() * half_dlon.sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+/// Marker for a missing level in hierarchical coordinates. A missing level
+/// matches nothing, not even another missing level, so a row without its leaf
+/// levels still matches other rows at the levels above.
+pub const MISSING_LEVEL: i32 = i32::MIN;
+
 #[inline]
 pub fn hierarchical(x: &[i32], y: &[i32]) -> i32 {
     x.iter()
         .rev()
         .zip(y.iter().rev())
-        .take_while(|(xi, yi)| xi != yi)
+        .take_while(|(xi, yi)| xi != yi || **xi == MISSING_LEVEL)
         .count() as i32
 }
 
+/// Dense symmetric table of distances between categories encoded as
+/// `0..size`, missing pairs are `NaN`.
+#[derive(Debug, PartialEq)]
+pub struct DistanceTable {
+    pub size: usize,
+    values: Vec<f32>,
+}
+
+impl DistanceTable {
+    /// Table with zeros on the diagonal and all other pairs missing.
+    pub fn new(size: usize) -> Self {
+        let mut values = vec![f32::NAN; size * size];
+        (0..size).for_each(|i| values[i * size + i] = 0.0);
+        Self { size, values }
+    }
+
+    #[inline]
+    pub fn get(&self, i: usize, j: usize) -> f32 {
+        self.values[i * self.size + j]
+    }
+
+    pub fn set(&mut self, i: usize, j: usize, d: f32) {
+        self.values[i * self.size + j] = d;
+        self.values[j * self.size + i] = d;
+    }
+
+    /// Shortest path distances from each of the `sources` to the first `size`
+    /// of the `nnodes` nodes in an undirected graph with weighted `edges`.
+    /// Unreachable nodes are at infinity.
+    pub fn from_graph(
+        size: usize,
+        nnodes: usize,
+        edges: &[(usize, usize, f32)],
+        sources: &[usize],
+    ) -> Self {
+        let mut adjacency: Vec<Vec<(usize, f32)>> = vec![Vec::new(); nnodes];
+        edges.iter().for_each(|(a, b, w)| {
+            adjacency[*a].push((*b, *w));
+            adjacency[*b].push((*a, *w));
+        });
+        let mut table = Self::new(size);
+        sources.iter().for_each(|source| {
+            let distance = dijkstra(&adjacency, *source);
+            (0..size).for_each(|j| table.set(*source, j, distance[j]));
+        });
+        table
+    }
+
+    /// First pair between the codes `a` and `b` whose distance is missing.
+    pub fn missing_pair(&self, a: &[usize], b: &[usize]) -> Option<(usize, usize)> {
+        a.iter()
+            .flat_map(|i| b.iter().map(move |j| (*i, *j)))
+            .find(|(i, j)| self.get(*i, *j).is_nan())
+    }
+
+    /// Whether all the finite distances are whole numbers.
+    pub fn is_integral(&self) -> bool {
+        self.values
+            .iter()
+            .all(|d| !d.is_finite() || d.fract() == 0.0)
+    }
+
+    /// Largest finite distance in the table.
+    pub fn max(&self) -> f32 {
+        self.values
+            .iter()
+            .filter(|d| d.is_finite())
+            .fold(0.0, |a, b| a.max(*b))
+    }
+}
+
+struct Visit {
+    distance: f32,
+    node: usize,
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    // reversed, so that the binary heap pops the closest node first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+fn dijkstra(adjacency: &[Vec<(usize, f32)>], source: usize) -> Vec<f32> {
+    let mut distance = vec![f32::INFINITY; adjacency.len()];
+    let mut heap = BinaryHeap::new();
+    distance[source] = 0.0;
+    heap.push(Visit {
+        distance: 0.0,
+        node: source,
+    });
+    while let Some(Visit { distance: d, node }) = heap.pop() {
+        if d > distance[node] {
+            continue;
+        }
+        adjacency[node].iter().for_each(|(next, w)| {
+            let d = d + w;
+            if d < distance[*next] {
+                distance[*next] = d;
+                heap.push(Visit {
+                    distance: d,
+                    node: *next,
+                });
+            }
+        });
+    }
+    distance
+}
+
+/// Distance metric between rows of continuous coordinates.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Metric {
+    #[default]
+    Euclidean,
+    Manhattan,
+    Chebyshev,
+    Minkowski(f32),
+    Haversine,
+    Cyclic(f32),
+    /// Look up the distance between the category codes in the first column.
+    Table(Arc<DistanceTable>),
+    /// Mahalanobis distance with the inverse of the covariance matrix.
+    Mahalanobis(Arc<Vec<f32>>),
+}
+
+impl Metric {
+    #[inline]
+    pub fn distance(&self, x: &[f32], y: &[f32]) -> f32 {
+        match self {
+            Self::Euclidean => euclidean(x, y),
+            Self::Manhattan => manhattan(x, y),
+            Self::Chebyshev => chebyshev(x, y),
+            Self::Minkowski(p) => minkowski(x, y, *p),
+            Self::Haversine => haversine(x, y),
+            Self::Cyclic(period) => cyclic(x, y, *period),
+            Self::Table(table) => table.get(x[0] as usize, y[0] as usize),
+            Self::Mahalanobis(precision) => mahalanobis(x, y, precision),
+        }
+    }
+
+    /// Upper bound of the distance between points whose coordinates differ
+    /// at most by `span` in each column.
+    pub fn bound(&self, span: &[f32]) -> f32 {
+        match self {
+            Self::Haversine => EARTH_RADIUS * std::f32::consts::PI,
+            Self::Cyclic(period) => {
+                let span: Vec<f32> = span.iter().map(|s| s.min(0.5 * period)).collect();
+                euclidean(&vec![0.0; span.len()], &span)
+            }
+            Self::Table(table) => table.max(),
+            Self::Mahalanobis(precision) => span
+                .iter()
+                .zip(precision.chunks(span.len()))
+                .map(|(si, row)| {
+                    si * row
+                        .iter()
+                        .zip(span.iter())
+                        .map(|(a, sj)| a.abs() * sj)
+                        .sum::<f32>()
+                })
+                .sum::<f32>()
+                .sqrt(),
+            _ => self.distance(&vec![0.0; span.len()], span),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -27,6 +323,128 @@ mod tests {
     }
 
     #[test]
+    fn test_euclidean_multi_cols() {
+        let x: Vec<f32> = vec![0.0, 0.0];
+        let y: Vec<f32> = vec![3.0, 4.0];
+        assert_eq!(euclidean(&x, &y), 5.0_f32);
+
+        let x: Vec<f32> = vec![1.0, 2.0, 3.0];
+        let y: Vec<f32> = vec![3.0, 5.0, 9.0];
+        assert_eq!(euclidean(&x, &y), 7.0_f32);
+    }
+
+    #[test]
+    fn test_metric() {
+        let x: Vec<f32> = vec![0.0, 0.0];
+        let y: Vec<f32> = vec![3.0, 4.0];
+
+        let my_distance: Vec<f32> = [
+            Metric::Euclidean,
+            Metric::Manhattan,
+            Metric::Chebyshev,
+            Metric::Minkowski(1.0),
+            Metric::Minkowski(2.0),
+        ]
+        .iter()
+        .map(|metric| metric.distance(&x, &y))
+        .collect();
+        let ok_distance: Vec<f32> = vec![5.0, 7.0, 4.0, 7.0, 5.0];
+        assert_eq!(my_distance, ok_distance);
+    }
+
+    #[test]
+    fn test_haversine() {
+        let pi = std::f32::consts::PI;
+        let x: Vec<f32> = vec![0.0, 0.0];
+        let y_vec = [vec![0.0, 0.0], vec![90.0, 0.0], vec![0.0, 180.0]];
+
+        let my_distance: Vec<f32> = y_vec.iter().map(|y| haversine(&x, y)).collect();
+        let ok_distance: Vec<f32> = vec![0.0, 0.5 * pi * EARTH_RADIUS, pi * EARTH_RADIUS];
+        for (my, ok) in my_distance.iter().zip(ok_distance.iter()) {
+            assert!((my - ok).abs() < 1e-3 * ok.max(1.0));
+        }
+
+        // neighbours across the antimeridian
+        let my_distance = haversine(&[0.0, 179.0], &[0.0, -179.0]);
+        let ok_distance = pi * EARTH_RADIUS / 90.0;
+        assert!((my_distance - ok_distance).abs() < 1e-3 * ok_distance);
+    }
+
+    #[test]
+    fn test_cyclic() {
+        let x: Vec<f32> = vec![12.0];
+        let y_vec = [vec![1.0], vec![6.0], vec![11.0], vec![25.0]];
+
+        let my_distance: Vec<f32> = y_vec.iter().map(|y| cyclic(&x, y, 12.0)).collect();
+        let ok_distance: Vec<f32> = vec![1.0, 6.0, 1.0, 1.0];
+        assert_eq!(my_distance, ok_distance);
+
+        let my_distance = cyclic(&[1.0, 23.0], &[12.0, 2.0], 24.0);
+        let ok_distance = (11.0_f32 * 11.0 + 3.0 * 3.0).sqrt();
+        assert_eq!(my_distance, ok_distance);
+        assert_eq!(Metric::Cyclic(12.0).bound(&[11.0]), 6.0);
+    }
+
+    #[test]
+    fn test_table() {
+        let mut table = DistanceTable::new(3);
+        table.set(0, 1, 2.0);
+        table.set(2, 1, 5.0);
+        let metric = Metric::Table(Arc::new(table));
+
+        assert_eq!(metric.distance(&[1.0], &[0.0]), 2.0);
+        assert_eq!(metric.distance(&[1.0], &[2.0]), 5.0);
+        assert_eq!(metric.distance(&[2.0], &[2.0]), 0.0);
+        assert!(metric.distance(&[0.0], &[2.0]).is_nan());
+        assert_eq!(metric.bound(&[2.0]), 5.0);
+
+        let Metric::Table(table) = metric else {
+            panic!("wrong metric");
+        };
+        assert_eq!(table.missing_pair(&[0, 1], &[1]), None);
+        assert_eq!(table.missing_pair(&[0, 1], &[1, 2]), Some((0, 2)));
+    }
+
+    #[test]
+    fn test_graph() {
+        // path 0 - 3 - 1 with a shortcut 0 - 1, and 2 disconnected
+        let edges = [(0, 3, 1.0), (3, 1, 1.0), (0, 1, 5.0)];
+        let table = DistanceTable::from_graph(3, 4, &edges, &[0, 2]);
+
+        let my_distance: Vec<f32> = (0..3).map(|j| table.get(0, j)).collect();
+        let ok_distance: Vec<f32> = vec![0.0, 2.0, f32::INFINITY];
+        assert_eq!(my_distance, ok_distance);
+        assert_eq!(table.get(1, 0), 2.0);
+        assert_eq!(table.get(2, 2), 0.0);
+        assert_eq!(table.missing_pair(&[0, 2], &[0, 1, 2]), None);
+        assert!(table.is_integral());
+
+        let table = DistanceTable::from_graph(2, 2, &[(0, 1, 0.5)], &[0]);
+        assert!(!table.is_integral());
+    }
+
+    #[test]
+    fn test_mahalanobis() {
+        let covariance = vec![4.0, 0.0, 0.0, 1.0];
+        let precision = invert(&covariance, 2).unwrap();
+        assert_eq!(precision, vec![0.25, 0.0, 0.0, 1.0]);
+        assert_eq!(
+            mahalanobis(&[0.0, 0.0], &[2.0, 1.0], &precision),
+            2.0_f32.sqrt()
+        );
+
+        let covariance = vec![2.0, 1.0, 1.0, 2.0];
+        let precision = invert(&covariance, 2).unwrap();
+        let my_distance = mahalanobis(&[0.0, 0.0], &[1.0, 1.0], &precision);
+        let ok_distance = (2.0_f32 / 3.0).sqrt();
+        assert!((my_distance - ok_distance).abs() < 1e-6);
+        assert!(Metric::Mahalanobis(Arc::new(precision)).bound(&[1.0, 1.0]) >= my_distance);
+
+        assert_eq!(invert(&[1.0, 2.0, 2.0, 4.0], 2), None);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
     fn test_hierarchical() {
         let x = vec![0, 1, 2];
         let y_vec = vec![vec![3, 4, 5], vec![0, 6, 7], vec![0, 1, 8], vec![0, 1, 2]];
@@ -35,4 +453,19 @@ mod tests {
         let ok_distance: Vec<i32> = vec![3, 2, 1, 0];
         assert_eq!(my_distance, ok_distance);
     }
+
+    #[test]
+    fn test_hierarchical_missing() {
+        let x = vec![0, 1, MISSING_LEVEL];
+        let y_vec = [
+            vec![0, 1, 2],
+            vec![0, 1, MISSING_LEVEL],
+            vec![0, 6, MISSING_LEVEL],
+            vec![3, MISSING_LEVEL, MISSING_LEVEL],
+        ];
+
+        let my_distance: Vec<i32> = y_vec.iter().map(|y| hierarchical(&x, y)).collect();
+        let ok_distance: Vec<i32> = vec![1, 1, 2, 3];
+        assert_eq!(my_distance, ok_distance);
+    }
 }
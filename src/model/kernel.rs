@@ -1,4 +1,5 @@
-use crate::model::distance::{euclidean, hierarchical};
+use crate::model::distance::{hierarchical, DistanceTable, Metric, MISSING_LEVEL};
+use std::sync::Arc;
 
 pub trait Kernel {
     type CType;
@@ -11,13 +12,32 @@ pub trait Kernel {
     }
 }
 
+/// Kernels with a length scale `radius`, which can be set for each
+/// prediction row.
+pub trait Rescale: Kernel + Sized {
+    fn with_radius(&self, radius: f32) -> Self;
+}
+
 pub struct Exponential {
     pub radius: f32,
+    pub metric: Metric,
 }
 impl Exponential {
     pub fn new(radius: f32) -> Self {
         assert!(radius > 0.0, "`radius` has to be positive");
-        Self { radius }
+        Self {
+            radius,
+            metric: Metric::default(),
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+impl Rescale for Exponential {
+    fn with_radius(&self, radius: f32) -> Self {
+        Self::new(radius).with_metric(self.metric.clone())
     }
 }
 impl Kernel for Exponential {
@@ -26,7 +46,7 @@ impl Kernel for Exponential {
 
     #[inline]
     fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
-        euclidean(x, y)
+        self.metric.distance(x, y)
     }
 
     #[inline]
@@ -38,12 +58,39 @@ impl Kernel for Exponential {
 pub struct Tricubic {
     pub radius: f32,
     pub exponent: f32,
+    pub metric: Metric,
+    /// Keep the negative weights beyond `radius` instead of clipping them
+    /// to zero.
+    pub allow_negative: bool,
 }
 impl Tricubic {
     pub fn new(radius: f32, exponent: f32) -> Self {
         assert!(radius > 0.0, "`radius` has to be positive");
         assert!(exponent >= 0.0, "`exponent` has to be non-negative");
-        Self { radius, exponent }
+        Self {
+            radius,
+            exponent,
+            metric: Metric::default(),
+            allow_negative: false,
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+
+    pub fn with_allow_negative(self, allow_negative: bool) -> Self {
+        Self {
+            allow_negative,
+            ..self
+        }
+    }
+}
+impl Rescale for Tricubic {
+    fn with_radius(&self, radius: f32) -> Self {
+        Self::new(radius, self.exponent)
+            .with_metric(self.metric.clone())
+            .with_allow_negative(self.allow_negative)
     }
 }
 impl Kernel for Tricubic {
@@ -52,21 +99,348 @@ impl Kernel for Tricubic {
 
     #[inline]
     fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
-        euclidean(x, y)
+        self.metric.distance(x, y)
     }
 
     #[inline]
     fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
         let x = 1.0 - (d / self.radius).powf(self.exponent);
+        if x < 0.0 && !self.allow_negative {
+            return 0.0;
+        }
         x * x * x
     }
 }
 
-pub struct Leveled {
+/// Rule for choosing the radius of an adaptive kernel from the distances
+/// between one prediction point and all data points.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bandwidth {
+    /// Maximum distance plus one, covering all data points.
+    Max,
+    /// Just beyond the distance to the k-th nearest data point, so that it
+    /// keeps a positive weight.
+    Nearest(usize),
+    /// Just beyond the distance to the nearest `ceil(q * n)` data points, the
+    /// LOESS span.
+    Quantile(f32),
+}
+
+impl Bandwidth {
+    pub fn radius(&self, distance: &[f32]) -> f32 {
+        let k = match self {
+            Self::Max => {
+                return distance
+                    .iter()
+                    .max_by(|x, y| x.partial_cmp(y).unwrap())
+                    .map(|x| x + 1.0)
+                    .unwrap()
+            }
+            Self::Nearest(k) => *k,
+            Self::Quantile(q) => (q * distance.len() as f32).ceil() as usize,
+        };
+        let k = k.clamp(1, distance.len());
+        let mut distance = distance.to_vec();
+        let (_, radius, _) =
+            distance.select_nth_unstable_by(k - 1, |x, y| x.partial_cmp(y).unwrap());
+        next_up(*radius)
+    }
+}
+
+/// Smallest float greater than the non-negative `x`.
+fn next_up(x: f32) -> f32 {
+    f32::from_bits(x.to_bits() + 1)
+}
+
+/// Tricubic kernel whose radius is chosen for each prediction point
+/// according to the [`Bandwidth`].
+pub struct AdaptiveTricubic {
+    pub tricubic: Tricubic,
+    pub bandwidth: Bandwidth,
+}
+impl AdaptiveTricubic {
+    pub fn new(tricubic: Tricubic, bandwidth: Bandwidth) -> Self {
+        match bandwidth {
+            Bandwidth::Nearest(k) => assert!(k >= 1, "`k` has to be positive"),
+            Bandwidth::Quantile(q) => {
+                assert!(q > 0.0 && q <= 1.0, "`quantile` has to be in (0, 1]")
+            }
+            Bandwidth::Max => {}
+        }
+        Self {
+            tricubic,
+            bandwidth,
+        }
+    }
+
+    pub fn with_radius(&self, radius: f32) -> Tricubic {
+        self.tricubic.with_radius(radius)
+    }
+}
+impl Kernel for AdaptiveTricubic {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.tricubic.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        self.tricubic.kernel_from_distance(d)
+    }
+}
+
+pub struct Gaussian {
+    pub radius: f32,
+    pub metric: Metric,
+    two_radius_squared: f32,
+}
+impl Gaussian {
+    pub fn new(radius: f32) -> Self {
+        assert!(radius > 0.0, "`radius` has to be positive");
+        Self {
+            radius,
+            metric: Metric::default(),
+            two_radius_squared: 2.0 * radius * radius,
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+impl Rescale for Gaussian {
+    fn with_radius(&self, radius: f32) -> Self {
+        Self::new(radius).with_metric(self.metric.clone())
+    }
+}
+impl Kernel for Gaussian {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.metric.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        (-(d * d / self.two_radius_squared)).exp()
+    }
+}
+
+/// Shape of a kernel that is exactly zero beyond its radius, in terms of
+/// `u = d / radius`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CompactShape {
+    /// `1 - u^2`
+    Epanechnikov,
+    /// `(1 - u^2)^2`, also known as quartic
+    Biweight,
+    /// `(1 - u^2)^3`
+    Triweight,
+    /// `1`, also known as boxcar
+    Uniform,
+}
+
+pub struct Compact {
+    pub radius: f32,
+    pub shape: CompactShape,
+    pub metric: Metric,
+}
+impl Compact {
+    pub fn new(radius: f32, shape: CompactShape) -> Self {
+        assert!(radius > 0.0, "`radius` has to be positive");
+        Self {
+            radius,
+            shape,
+            metric: Metric::default(),
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+impl Rescale for Compact {
+    fn with_radius(&self, radius: f32) -> Self {
+        Self::new(radius, self.shape).with_metric(self.metric.clone())
+    }
+}
+impl Kernel for Compact {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.metric.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        let u = d / self.radius;
+        if u > 1.0 {
+            return 0.0;
+        }
+        let x = 1.0 - u * u;
+        match self.shape {
+            CompactShape::Epanechnikov => x,
+            CompactShape::Biweight => x * x,
+            CompactShape::Triweight => x * x * x,
+            CompactShape::Uniform => 1.0,
+        }
+    }
+}
+
+/// Smoothness `nu` of a Matérn kernel, the half-integer values with a closed
+/// form.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MaternSmoothness {
+    Half,
+    ThreeHalves,
+    FiveHalves,
+}
+
+/// Matérn kernel with length scale `radius`.
+pub struct Matern {
     pub radius: f32,
+    pub nu: MaternSmoothness,
+    pub metric: Metric,
+}
+impl Matern {
+    pub fn new(radius: f32, nu: MaternSmoothness) -> Self {
+        assert!(radius > 0.0, "`radius` has to be positive");
+        Self {
+            radius,
+            nu,
+            metric: Metric::default(),
+        }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+impl Rescale for Matern {
+    fn with_radius(&self, radius: f32) -> Self {
+        Self::new(radius, self.nu).with_metric(self.metric.clone())
+    }
+}
+impl Kernel for Matern {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.metric.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        let u = d / self.radius;
+        // `(1 + inf) * exp(-inf)` is NaN, e.g. for unreachable graph nodes
+        if !u.is_finite() {
+            return 0.0;
+        }
+        match self.nu {
+            MaternSmoothness::Half => (-u).exp(),
+            MaternSmoothness::ThreeHalves => {
+                let v = 3.0_f32.sqrt() * u;
+                (1.0 + v) * (-v).exp()
+            }
+            MaternSmoothness::FiveHalves => {
+                let v = 5.0_f32.sqrt() * u;
+                (1.0 + v + v * v / 3.0) * (-v).exp()
+            }
+        }
+    }
+}
+
+/// Inverse-distance weighting `1 / (d + eps)^power`, with the weights capped
+/// at `max_weight` to limit the weight of exact matches.
+pub struct InverseDistance {
+    pub power: f32,
+    pub eps: f32,
+    pub max_weight: f32,
+    pub metric: Metric,
+}
+impl InverseDistance {
+    pub fn new(power: f32, eps: f32) -> Self {
+        assert!(power > 0.0, "`power` has to be positive");
+        assert!(eps >= 0.0, "`eps` has to be non-negative");
+        Self {
+            power,
+            eps,
+            max_weight: f32::INFINITY,
+            metric: Metric::default(),
+        }
+    }
+
+    pub fn with_max_weight(self, max_weight: f32) -> Self {
+        Self { max_weight, ..self }
+    }
+
+    pub fn with_metric(self, metric: Metric) -> Self {
+        Self { metric, ..self }
+    }
+}
+impl Kernel for InverseDistance {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.metric.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        (d + self.eps).powf(-self.power).min(self.max_weight)
+    }
+}
+
+/// Weight one for rows with equal categories in all columns, `mismatch`
+/// otherwise. Missing categories match nothing.
+pub struct Indicator {
+    pub mismatch: f32,
+}
+impl Indicator {
+    pub fn new(mismatch: f32) -> Self {
+        assert!(mismatch >= 0.0, "`mismatch` has to be non-negative");
+        Self { mismatch }
+    }
+}
+impl Kernel for Indicator {
+    type CType = i32;
+    type DType = bool;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        x.iter()
+            .zip(y.iter())
+            .any(|(xi, yi)| xi != yi || *xi == MISSING_LEVEL)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        if *d {
+            self.mismatch
+        } else {
+            1.0
+        }
+    }
+}
+
+pub struct Leveled {
     pub maxlvl: i32,
-    one_minus_radius: f32,
-    maxlvl_minus_one: i32,
+    /// Weight for each distance level, the last one is for rows that match
+    /// at no level.
+    pub weights: Vec<f32>,
+    /// Distance levels looked up from the codes in the first column instead
+    /// of `hierarchical` distance.
+    pub table: Option<Arc<DistanceTable>>,
 }
 
 impl Leveled {
@@ -74,12 +448,47 @@ impl Leveled {
         assert!(radius > 0.0, "`radius` has to be positive");
         assert!(maxlvl >= 1, "`maxlvl` has to be non-less than one");
         let one_minus_radius = 1.0 - radius;
-        let maxlvl_minus_one = maxlvl - 1;
+        let mut weights: Vec<f32> = (0..maxlvl)
+            .map(|d| {
+                let mut result: f32 = (0..d).map(|_| &one_minus_radius).product();
+                if d < maxlvl - 1 {
+                    result *= &radius;
+                }
+                result
+            })
+            .collect();
+        weights.push(0.0);
         Self {
-            radius,
             maxlvl,
-            one_minus_radius,
-            maxlvl_minus_one,
+            weights,
+            table: None,
+        }
+    }
+
+    /// Leveled kernel with explicit weights for distance `0..maxlvl`, and
+    /// optionally one more weight for rows that match at no level.
+    pub fn from_weights(mut weights: Vec<f32>, maxlvl: i32) -> Self {
+        assert!(maxlvl >= 1, "`maxlvl` has to be non-less than one");
+        assert!(
+            weights.len() == maxlvl as usize || weights.len() == maxlvl as usize + 1,
+            "`weights` has to have `maxlvl` or `maxlvl + 1` elements"
+        );
+        assert!(
+            weights.iter().all(|w| *w >= 0.0),
+            "`weights` has to be non-negative"
+        );
+        weights.resize(maxlvl as usize + 1, 0.0);
+        Self {
+            maxlvl,
+            weights,
+            table: None,
+        }
+    }
+
+    pub fn with_table(self, table: Arc<DistanceTable>) -> Self {
+        Self {
+            table: Some(table),
+            ..self
         }
     }
 }
@@ -89,22 +498,175 @@ impl Kernel for Leveled {
 
     #[inline]
     fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
-        hierarchical(x, y)
+        match &self.table {
+            Some(table) => table
+                .get(x[0] as usize, y[0] as usize)
+                .min(self.maxlvl as f32) as i32,
+            None => hierarchical(x, y),
+        }
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        self.weights[(*d).min(self.maxlvl) as usize]
+    }
+}
+
+/// Kernel on the signed difference `x - y` of a single coordinate, with
+/// separate kernels for data before and after the prediction point. Without
+/// an `after` kernel it is causal, and data after the prediction point gets
+/// zero weight.
+pub struct Asymmetric {
+    pub before: BoxedKernel,
+    pub after: Option<BoxedKernel>,
+}
+impl Asymmetric {
+    pub fn new(before: BoxedKernel, after: Option<BoxedKernel>) -> Self {
+        Self { before, after }
+    }
+}
+impl Kernel for Asymmetric {
+    type CType = f32;
+    type DType = f32;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        x[0] - y[0]
     }
 
     #[inline]
     fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
-        if d >= &self.maxlvl {
-            0.0
+        if *d >= 0.0 {
+            self.before.kernel_from_distance(d)
         } else {
-            let mut result: f32 = (0..*d).map(|_| &self.one_minus_radius).product();
-            if d < &self.maxlvl_minus_one {
-                result *= &self.radius;
-            }
-            result
+            self.after
+                .as_ref()
+                .map_or(0.0, |after| after.kernel_from_distance(&-d))
+        }
+    }
+}
+
+/// Weighted sum of kernels plus a `constant`. All kernels are evaluated on
+/// the distance of the first kernel, so they should share one metric.
+pub struct Sum<K: Kernel> {
+    pub kernels: Vec<K>,
+    pub weights: Vec<f32>,
+    pub constant: f32,
+}
+impl<K: Kernel> Sum<K> {
+    pub fn new(kernels: Vec<K>, weights: Vec<f32>) -> Self {
+        assert!(!kernels.is_empty(), "`kernels` can't be empty");
+        assert_eq!(kernels.len(), weights.len(), "one weight per kernel");
+        Self {
+            kernels,
+            weights,
+            constant: 0.0,
+        }
+    }
+
+    pub fn with_constant(self, constant: f32) -> Self {
+        Self { constant, ..self }
+    }
+}
+impl<K: Kernel> Kernel for Sum<K> {
+    type CType = K::CType;
+    type DType = K::DType;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.kernels[0].distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        self.kernels
+            .iter()
+            .zip(self.weights.iter())
+            .map(|(kernel, w)| w * kernel.kernel_from_distance(d))
+            .sum::<f32>()
+            + self.constant
+    }
+}
+
+/// Product of kernels, evaluated on the distance of the first kernel.
+pub struct Product<K: Kernel> {
+    pub kernels: Vec<K>,
+}
+impl<K: Kernel> Product<K> {
+    pub fn new(kernels: Vec<K>) -> Self {
+        assert!(!kernels.is_empty(), "`kernels` can't be empty");
+        Self { kernels }
+    }
+}
+impl<K: Kernel> Kernel for Product<K> {
+    type CType = K::CType;
+    type DType = K::DType;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.kernels[0].distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        self.kernels
+            .iter()
+            .map(|kernel| kernel.kernel_from_distance(d))
+            .product()
+    }
+}
+
+/// Kernel with its weights clamped to `[floor, ceiling]`, e.g. to keep a
+/// minimum weight for distant data.
+pub struct Clamp<K: Kernel> {
+    pub kernel: K,
+    pub floor: f32,
+    pub ceiling: f32,
+}
+impl<K: Kernel> Clamp<K> {
+    pub fn new(kernel: K, floor: f32, ceiling: f32) -> Self {
+        assert!(floor <= ceiling, "`floor` can't exceed `ceiling`");
+        Self {
+            kernel,
+            floor,
+            ceiling,
         }
     }
 }
+impl<K: Kernel> Kernel for Clamp<K> {
+    type CType = K::CType;
+    type DType = K::DType;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        self.kernel.distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        self.kernel
+            .kernel_from_distance(d)
+            .clamp(self.floor, self.ceiling)
+    }
+}
+
+/// Continuous kernel chosen at runtime, e.g. from the config.
+pub type BoxedKernel = Box<dyn Kernel<CType = f32, DType = f32> + Send + Sync>;
+
+impl<K: Kernel + ?Sized> Kernel for Box<K> {
+    type CType = K::CType;
+    type DType = K::DType;
+
+    #[inline]
+    fn distance(&self, x: &[Self::CType], y: &[Self::CType]) -> Self::DType {
+        (**self).distance(x, y)
+    }
+
+    #[inline]
+    fn kernel_from_distance(&self, d: &Self::DType) -> f32 {
+        (**self).kernel_from_distance(d)
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -129,6 +691,208 @@ mod tests {
     }
 
     #[test]
+    fn test_boxed() {
+        let kernel: BoxedKernel = Box::new(Gaussian::new(2.0));
+
+        let my_weight = kernel.kernel(&[0.0, 1.0], &[2.0, 1.0]);
+        let ok_weight = Gaussian::new(2.0).kernel_from_distance(&2.0);
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    fn test_asymmetric() {
+        let kernel = Asymmetric::new(
+            Box::new(Exponential::new(2.0)),
+            Some(Box::new(Exponential::new(0.5))),
+        );
+
+        let my_weight: Vec<f32> = [[2000.0], [2001.0], [2003.0]]
+            .iter()
+            .map(|y| kernel.kernel(&[2001.0], y))
+            .collect();
+        let ok_weight: Vec<f32> = vec![(-0.5_f32).exp(), 1.0, (-4.0_f32).exp()];
+        assert_eq!(my_weight, ok_weight);
+
+        let kernel = Asymmetric::new(Box::new(Exponential::new(2.0)), None);
+        assert_eq!(kernel.kernel(&[2001.0], &[2000.0]), (-0.5_f32).exp());
+        assert_eq!(kernel.kernel(&[2001.0], &[2003.0]), 0.0);
+    }
+
+    #[test]
+    fn test_composite() {
+        let kernel = Sum::new(vec![Exponential::new(1.0)], vec![0.8]).with_constant(0.2);
+        assert_eq!(kernel.kernel_from_distance(&0.0), 1.0);
+        assert_eq!(
+            kernel.kernel_from_distance(&1.0),
+            0.8 * (-1.0_f32).exp() + 0.2
+        );
+
+        let kernel = Product::new(vec![
+            Compact::new(2.0, CompactShape::Epanechnikov),
+            Compact::new(1.0, CompactShape::Uniform),
+        ]);
+        assert_eq!(kernel.kernel_from_distance(&1.0), 0.75);
+        assert_eq!(kernel.kernel_from_distance(&1.5), 0.0);
+
+        let kernel = Clamp::new(Exponential::new(1.0), 0.1, 0.5);
+        let my_weight: Vec<f32> = [0.0, 1.0, 10.0]
+            .iter()
+            .map(|d| kernel.kernel_from_distance(d))
+            .collect();
+        let ok_weight: Vec<f32> = vec![0.5, (-1.0_f32).exp(), 0.1];
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    fn test_matern() {
+        let weight = |nu| Matern::new(2.0, nu).kernel_from_distance(&1.0);
+
+        let ok_weight = (-0.5_f32).exp();
+        assert!((weight(MaternSmoothness::Half) - ok_weight).abs() < 1e-6);
+
+        let ok_weight = 0.784_887_7;
+        assert!((weight(MaternSmoothness::ThreeHalves) - ok_weight).abs() < 1e-6);
+
+        let ok_weight = 0.828_649_1;
+        assert!((weight(MaternSmoothness::FiveHalves) - ok_weight).abs() < 1e-6);
+
+        assert_eq!(
+            Matern::new(2.0, MaternSmoothness::FiveHalves).kernel_from_distance(&0.0),
+            1.0
+        );
+
+        let weight = |nu| Matern::new(2.0, nu).kernel_from_distance(&f32::INFINITY);
+        assert_eq!(weight(MaternSmoothness::Half), 0.0);
+        assert_eq!(weight(MaternSmoothness::ThreeHalves), 0.0);
+        assert_eq!(weight(MaternSmoothness::FiveHalves), 0.0);
+    }
+
+    #[test]
+    fn test_inverse_distance() {
+        let kernel = InverseDistance::new(2.0, 0.5);
+        let my_weight: Vec<f32> = [0.0, 1.5]
+            .iter()
+            .map(|d| kernel.kernel_from_distance(d))
+            .collect();
+        let ok_weight: Vec<f32> = vec![4.0, 0.25];
+        assert_eq!(my_weight, ok_weight);
+
+        let kernel = InverseDistance::new(1.0, 0.0).with_max_weight(10.0);
+        assert_eq!(kernel.kernel_from_distance(&0.0), 10.0);
+        assert_eq!(kernel.kernel_from_distance(&0.5), 2.0);
+    }
+
+    #[test]
+    fn test_rescale() {
+        let kernel = Gaussian::new(1.0).with_metric(Metric::Manhattan);
+        let rescaled = kernel.with_radius(2.0);
+        assert_eq!(rescaled.metric, Metric::Manhattan);
+        assert_eq!(
+            rescaled.kernel_from_distance(&2.0),
+            Gaussian::new(2.0).kernel_from_distance(&2.0)
+        );
+
+        let tricubic = Tricubic::new(1.0, 3.0).with_metric(Metric::Manhattan);
+        let kernel = AdaptiveTricubic::new(tricubic, Bandwidth::Max);
+        assert_eq!(kernel.with_radius(2.0).metric, Metric::Manhattan);
+    }
+
+    #[test]
+    fn test_tricubic_outside_radius() {
+        let kernel = Tricubic::new(1.0, 1.0);
+        assert_eq!(kernel.kernel_from_distance(&2.0), 0.0);
+
+        let kernel = kernel.with_allow_negative(true);
+        assert_eq!(kernel.kernel_from_distance(&2.0), -1.0);
+    }
+
+    #[test]
+    fn test_bandwidth() {
+        let distance = [3.0, 0.0, 2.0, 1.0];
+
+        let my_radius: Vec<f32> = [
+            Bandwidth::Max,
+            Bandwidth::Nearest(2),
+            Bandwidth::Nearest(8),
+            Bandwidth::Quantile(0.5),
+            Bandwidth::Quantile(0.6),
+        ]
+        .iter()
+        .map(|bandwidth| bandwidth.radius(&distance))
+        .collect();
+        let ok_radius: Vec<f32> = vec![4.0, next_up(1.0), next_up(3.0), next_up(1.0), next_up(2.0)];
+        assert_eq!(my_radius, ok_radius);
+    }
+
+    #[test]
+    fn test_gaussian() {
+        let kernel = Gaussian::new(2.0);
+
+        let my_weight: Vec<f32> = [0.0, 2.0, 4.0]
+            .iter()
+            .map(|d| kernel.kernel_from_distance(d))
+            .collect();
+        let ok_weight: Vec<f32> = vec![1.0, (-0.5_f32).exp(), (-2.0_f32).exp()];
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    fn test_compact() {
+        let distance = [0.0, 1.0, 2.0, 3.0];
+        let shapes = [
+            CompactShape::Epanechnikov,
+            CompactShape::Biweight,
+            CompactShape::Triweight,
+            CompactShape::Uniform,
+        ];
+
+        let my_weight: Vec<Vec<f32>> = shapes
+            .iter()
+            .map(|shape| {
+                let kernel = Compact::new(2.0, *shape);
+                distance
+                    .iter()
+                    .map(|d| kernel.kernel_from_distance(d))
+                    .collect()
+            })
+            .collect();
+        let ok_weight: Vec<Vec<f32>> = vec![
+            vec![1.0, 0.75, 0.0, 0.0],
+            vec![1.0, 0.5625, 0.0, 0.0],
+            vec![1.0, 0.421875, 0.0, 0.0],
+            vec![1.0, 1.0, 1.0, 0.0],
+        ];
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    fn test_multi_cols() {
+        let x: Vec<f32> = vec![0.0, 0.0];
+        let y: Vec<f32> = vec![3.0, 4.0];
+        let my_weight = Exponential::new(5.0).kernel(&x, &y);
+        let ok_weight = (-1.0_f32).exp();
+        assert_eq!(my_weight, ok_weight);
+
+        let x: Vec<f32> = vec![1.0, 2.0, 3.0];
+        let y: Vec<f32> = vec![3.0, 5.0, 9.0];
+        let my_weight = Tricubic::new(28.0, 1.0).kernel(&x, &y);
+        let ok_weight = 0.421875_f32;
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    fn test_metric() {
+        let x: Vec<f32> = vec![0.0, 0.0];
+        let y: Vec<f32> = vec![3.0, 4.0];
+        let kernel = Exponential::new(7.0).with_metric(Metric::Manhattan);
+
+        let my_weight = kernel.kernel(&x, &y);
+        let ok_weight = (-1.0_f32).exp();
+        assert_eq!(my_weight, ok_weight);
+    }
+
+    #[test]
+    #[allow(clippy::useless_vec)]
     fn test_depth_codem() {
         let kenerl = Leveled::new(0.5, 3);
         let distance = vec![0, 1, 2, 3];
@@ -140,4 +904,43 @@ mod tests {
         let ok_weight: Vec<f32> = vec![0.5, 0.25, 0.25, 0.0];
         assert_eq!(my_weight, ok_weight);
     }
+
+    #[test]
+    fn test_indicator() {
+        let kernel = Indicator::new(0.1);
+        let x = [1, 2];
+        let y_vec = [[1, 2], [1, 3], [MISSING_LEVEL, 2]];
+
+        let my_weight: Vec<f32> = y_vec.iter().map(|y| kernel.kernel(&x, y)).collect();
+        let ok_weight: Vec<f32> = vec![1.0, 0.1, 0.1];
+        assert_eq!(my_weight, ok_weight);
+        assert_eq!(kernel.kernel(&y_vec[2], &y_vec[2]), 0.1);
+    }
+
+    #[test]
+    fn test_leveled_weights() {
+        let kernel = Leveled::from_weights(vec![1.0, 0.6, 0.2, 0.05], 3);
+        let distance = [0, 1, 2, 3];
+
+        let my_weight: Vec<f32> = distance
+            .iter()
+            .map(|d| kernel.kernel_from_distance(d))
+            .collect();
+        let ok_weight: Vec<f32> = vec![1.0, 0.6, 0.2, 0.05];
+        assert_eq!(my_weight, ok_weight);
+
+        let kernel = Leveled::from_weights(vec![1.0, 0.6, 0.2], 3);
+        assert_eq!(kernel.kernel_from_distance(&3), 0.0);
+
+        let mut table = DistanceTable::new(3);
+        table.set(0, 1, 1.0);
+        table.set(0, 2, f32::INFINITY);
+        let kernel = Leveled::from_weights(vec![1.0, 0.5], 2).with_table(Arc::new(table));
+        let my_weight: Vec<f32> = [0, 1, 2]
+            .iter()
+            .map(|y| kernel.kernel(&[0], &[*y]))
+            .collect();
+        let ok_weight: Vec<f32> = vec![1.0, 0.5, 0.0];
+        assert_eq!(my_weight, ok_weight);
+    }
 }